@@ -12,8 +12,16 @@ function extractIpFromMultiaddr(addr) {
   return match ? match[1] : null;
 }
 
+// Time zone to show times in; the browser's own when unset.
+const TIME_ZONE = import.meta.env.VITE_TIME_ZONE || undefined;
+
+// The server reports times as milliseconds since the Unix epoch.
+function formatTime(timestamp) {
+  return new Date(timestamp).toLocaleString(undefined, { timeZone: TIME_ZONE });
+}
+
 function timeAgo(timestamp) {
-  const diff = Math.floor(Date.now() / 1000) - timestamp;
+  const diff = Math.floor((Date.now() - timestamp) / 1000);
   if (diff < 60) return `${diff} seconds ago`;
   const minutes = Math.floor(diff / 60);
   return `${minutes} minute${minutes !== 1 ? 's' : ''} ago`;
@@ -56,7 +64,7 @@ export default function PeerDashboard() {
         <div key={peer.peer} style={{ border: "1px solid #ccc", margin: "1rem 0", padding: "1rem", borderRadius: "8px" }}>
           <div><strong>Peer:</strong> {peer.peer}</div>
           <div><strong>Ping:</strong> {peer.ping} ms</div>
-          <div><strong>Last Seen:</strong> {new Date(peer.last_seen * 1000).toLocaleString()} ({timeAgo(peer.last_seen)})</div>
+          <div><strong>Last Seen:</strong> {formatTime(peer.last_seen)} ({timeAgo(peer.last_seen)})</div>
           <div>
             <strong>Addresses:</strong>
             <ul>
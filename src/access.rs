@@ -0,0 +1,197 @@
+use std::collections::{HashMap, HashSet};
+
+use libp2p::PeerId;
+use serde::Serialize;
+
+use crate::{parse_peer_ids, PeerStat};
+
+/// Which peers may connect and register.
+///
+/// With `BOTUN_AURA_ALLOW_PEERS` set the server runs in allow-list mode and
+/// only the listed peers are accepted. Peers in `BOTUN_AURA_DENY_PEERS` are
+/// refused in either mode.
+#[derive(Debug, Clone, Default)]
+pub struct AccessList {
+    /// `Some` in allow-list mode.
+    pub allowed: Option<HashSet<PeerId>>,
+    pub denied: HashSet<PeerId>,
+}
+
+/// How the lists differ between two [`AccessList`]s, as returned by
+/// `POST /admin/reload`.
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct AccessChanges {
+    pub allowed_added: Vec<String>,
+    pub allowed_removed: Vec<String>,
+    pub denied_added: Vec<String>,
+    pub denied_removed: Vec<String>,
+    /// Whether allow-list mode was switched on or off.
+    pub allow_mode_changed: bool,
+}
+
+impl AccessList {
+    /// Reads `BOTUN_AURA_ALLOW_PEERS` and `BOTUN_AURA_DENY_PEERS` through `lookup`.
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, String> {
+        let allowed = lookup("BOTUN_AURA_ALLOW_PEERS").filter(|s| !s.trim().is_empty());
+        let denied = lookup("BOTUN_AURA_DENY_PEERS").unwrap_or_default();
+
+        Ok(AccessList {
+            allowed: allowed.map(|s| parse_peer_ids("BOTUN_AURA_ALLOW_PEERS", &s)).transpose()?,
+            denied: parse_peer_ids("BOTUN_AURA_DENY_PEERS", &denied)?,
+        })
+    }
+
+    pub fn changes(&self, new: &AccessList) -> AccessChanges {
+        fn sorted<'a>(peers: impl Iterator<Item = &'a PeerId>) -> Vec<String> {
+            let mut peers = peers.map(PeerId::to_string).collect::<Vec<_>>();
+            peers.sort();
+            peers
+        }
+
+        let empty = HashSet::new();
+        let (old_allowed, new_allowed) = (self.allowed.as_ref().unwrap_or(&empty), new.allowed.as_ref().unwrap_or(&empty));
+
+        AccessChanges {
+            allowed_added: sorted(new_allowed.difference(old_allowed)),
+            allowed_removed: sorted(old_allowed.difference(new_allowed)),
+            denied_added: sorted(new.denied.difference(&self.denied)),
+            denied_removed: sorted(self.denied.difference(&new.denied)),
+            allow_mode_changed: self.allowed.is_some() != new.allowed.is_some(),
+        }
+    }
+
+    pub fn is_allowed(&self, peer: &PeerId) -> bool {
+        !self.denied.contains(peer) && self.allowed.as_ref().is_none_or(|allowed| allowed.contains(peer))
+    }
+
+    /// Adds `peer` to the deny list. Returns whether it was newly denied.
+    pub fn deny(&mut self, peer: PeerId) -> bool {
+        self.denied.insert(peer)
+    }
+
+    /// Removes every peer that is no longer allowed from `peers`, returning
+    /// them with the namespaces they were registered under.
+    pub fn evict_disallowed(&self, peers: &mut HashMap<PeerId, PeerStat>) -> Vec<(PeerId, Vec<String>)> {
+        let disallowed = peers.keys().filter(|peer| !self.is_allowed(peer)).copied().collect::<Vec<_>>();
+
+        disallowed
+            .into_iter()
+            .filter_map(|peer| peers.remove(&peer).map(|stat| (peer, stat)))
+            .map(|(peer, stat)| (peer, stat.namespaces.into_iter().map(|ns| ns.namespace).collect()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peers_with(ids: &[PeerId]) -> HashMap<PeerId, PeerStat> {
+        ids.iter()
+            .map(|peer| {
+                let mut stat = PeerStat::new(*peer, 0);
+                stat.register(&libp2p::rendezvous::Namespace::from_static("test"), 7200, 0);
+                (*peer, stat)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn open_mode_allows_everyone_not_denied() {
+        let (a, b) = (PeerId::random(), PeerId::random());
+        let access = AccessList {
+            allowed: None,
+            denied: HashSet::from([b]),
+        };
+
+        assert!(access.is_allowed(&a));
+        assert!(!access.is_allowed(&b));
+    }
+
+    #[test]
+    fn allow_mode_allows_only_listed_peers() {
+        let (a, b, c) = (PeerId::random(), PeerId::random(), PeerId::random());
+        let access = AccessList {
+            allowed: Some(HashSet::from([a, c])),
+            denied: HashSet::from([c]),
+        };
+
+        assert!(access.is_allowed(&a));
+        assert!(!access.is_allowed(&b));
+        // Denying wins over allowing.
+        assert!(!access.is_allowed(&c));
+    }
+
+    #[test]
+    fn denying_a_peer_evicts_its_registration() {
+        let (a, b) = (PeerId::random(), PeerId::random());
+        let mut peers = peers_with(&[a, b]);
+        let mut access = AccessList::default();
+
+        assert!(access.evict_disallowed(&mut peers).is_empty());
+
+        assert!(access.deny(b));
+        assert!(!access.deny(b));
+        let evicted = access.evict_disallowed(&mut peers);
+
+        assert_eq!(evicted, vec![(b, vec!["test".to_string()])]);
+        assert!(peers.contains_key(&a));
+        assert!(!peers.contains_key(&b));
+    }
+
+    #[test]
+    fn reads_lists_through_lookup() {
+        let (a, b) = (PeerId::random(), PeerId::random());
+        let env = HashMap::from([
+            ("BOTUN_AURA_ALLOW_PEERS", format!("{a}, {b}")),
+            ("BOTUN_AURA_DENY_PEERS", String::new()),
+        ]);
+
+        let access = AccessList::from_lookup(|name| env.get(name).cloned()).unwrap();
+
+        assert_eq!(access.allowed, Some(HashSet::from([a, b])));
+        assert!(access.denied.is_empty());
+        assert!(AccessList::from_lookup(|_| Some("nonsense".to_string())).is_err());
+    }
+
+    #[test]
+    fn changes_between_lists() {
+        let (a, b) = (PeerId::random(), PeerId::random());
+        let old = AccessList {
+            allowed: None,
+            denied: HashSet::from([a]),
+        };
+        let new = AccessList {
+            allowed: Some(HashSet::from([a])),
+            denied: HashSet::from([b]),
+        };
+
+        assert_eq!(
+            old.changes(&new),
+            AccessChanges {
+                allowed_added: vec![a.to_string()],
+                allowed_removed: vec![],
+                denied_added: vec![b.to_string()],
+                denied_removed: vec![a.to_string()],
+                allow_mode_changed: true,
+            }
+        );
+        assert_eq!(new.changes(&new), AccessChanges::default());
+    }
+
+    #[test]
+    fn allow_mode_evicts_unlisted_registrations() {
+        let (a, b) = (PeerId::random(), PeerId::random());
+        let mut peers = peers_with(&[a, b]);
+        let access = AccessList {
+            allowed: Some(HashSet::from([a])),
+            denied: HashSet::new(),
+        };
+
+        let evicted = access.evict_disallowed(&mut peers);
+
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].0, b);
+        assert_eq!(peers.keys().collect::<Vec<_>>(), vec![&a]);
+    }
+}
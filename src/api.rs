@@ -0,0 +1,883 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet},
+    net::IpAddr,
+    path::PathBuf,
+    sync::{atomic::{self, AtomicI64}, Arc, Mutex},
+};
+
+use axum::{
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use libp2p::{multiaddr::Protocol, Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
+use tower_http::services::ServeDir;
+
+use crate::{
+    access::AccessChanges, auth::{self, BasicAuth}, connections::{ConnectedPeer, Connections}, enquirers::{EnquirerStat, Enquirers}, health::{HealthChecks, Signals}, history::History, info::NodeInfo, iplimit::IpCounts, metrics::Metrics,
+    negotiate::negotiated, now_ms, selfcheck::SelfCheck, snapshot::{Export, Import, ValidPeer, IMPORT_LIMIT_BYTES}, NatStatus, PeerRole, PeerStat,
+};
+
+/// Requests from the HTTP API that need the swarm, handled by the main loop.
+pub enum Command {
+    Deny { peer: PeerId, reply: oneshot::Sender<DenyOutcome> },
+    /// Closes the peer's connections, keeping its registrations. Replies
+    /// whether the peer was connected.
+    Disconnect { peer: PeerId, reply: oneshot::Sender<bool> },
+    Reload { reply: oneshot::Sender<Result<ReloadOutcome, String>> },
+    /// Merges validated entries into the peer table.
+    Import { peers: Vec<ValidPeer>, reply: oneshot::Sender<ImportOutcome> },
+}
+
+/// What became of the entries of an import. Peers already in the table keep
+/// their live entry.
+#[derive(Serialize, Debug, Default)]
+pub struct ImportOutcome {
+    pub imported: usize,
+    pub already_present: usize,
+    pub denied: usize,
+    /// Entries whose registrations had all run out.
+    pub expired: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DenyOutcome {
+    pub peer: String,
+    pub newly_denied: bool,
+    pub evicted_namespaces: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReloadOutcome {
+    pub access: AccessChanges,
+    pub max_namespaces_per_peer: Option<usize>,
+    /// Peers removed because the new lists no longer allow them, with the
+    /// namespaces they were registered under.
+    pub evicted: BTreeMap<String, Vec<String>>,
+    pub restart_required: bool,
+}
+
+/// Everything the HTTP API reads from or sends to the rest of the server.
+#[derive(Clone)]
+pub struct AppState {
+    pub peers: Arc<Mutex<HashMap<PeerId, PeerStat>>>,
+    pub history: Arc<Mutex<History>>,
+    pub enquirers: Arc<Mutex<Enquirers>>,
+    pub node_info: Arc<Mutex<NodeInfo>>,
+    pub metrics: Arc<Metrics>,
+    pub commands: mpsc::Sender<Command>,
+    pub basic_auth: Option<Arc<BasicAuth>>,
+    /// Latest self-check result, when `BOTUN_AURA_SELF_CHECK` is enabled.
+    pub self_check: Option<Arc<Mutex<SelfCheck>>>,
+    pub ip_counts: IpCounts,
+    pub connections: Arc<Mutex<Connections>>,
+    pub health: Arc<HealthChecks>,
+    /// When the swarm loop last started an iteration, in epoch milliseconds.
+    pub loop_heartbeat: Arc<AtomicI64>,
+    /// Directories the dashboard is served from, looked up in order; empty
+    /// in API-only mode.
+    pub static_dirs: Vec<PathBuf>,
+}
+
+pub fn build_router(state: AppState) -> Router {
+    let mut app = Router::new()
+        .route("/peers", get(peers))
+        .route("/peers/libp2p", get(peers_libp2p))
+        .route("/peers/expiring", get(peers_expiring))
+        .route("/peers/by-namespace", get(peers_by_namespace))
+        .route("/peers/ids", get(peer_ids))
+        .route("/peers/{id}", get(peer))
+        .route("/stats", get(stats))
+        .route("/stats/history", get(stats_history))
+        .route("/stats/enquirers", get(stats_enquirers))
+        .route("/info", get(info))
+        .route("/bootstrap", get(bootstrap))
+        .route("/metrics", get(metrics))
+        .route("/connections", get(connections))
+        .route("/debug/connections-per-ip", get(connections_per_ip));
+
+    // Admin routes change server state, so they only exist behind authentication.
+    if state.basic_auth.is_some() {
+        app = app
+            .route("/admin/peers/{id}/deny", post(deny_peer))
+            .route("/admin/peers/{id}/disconnect", post(disconnect_peer))
+            .route("/admin/reload", post(reload))
+            .route("/admin/export", get(export))
+            .route("/admin/import", post(import).layer(DefaultBodyLimit::max(IMPORT_LIMIT_BYTES)));
+    }
+
+    let app = match static_files(&state.static_dirs) {
+        Some(static_files) => app.fallback_service(static_files),
+        None => app.fallback(not_found),
+    };
+    let mut app = app.with_state(state.clone());
+
+    if let Some(auth) = &state.basic_auth {
+        app = app.layer(axum::middleware::from_fn_with_state(auth.clone(), auth::require_basic_auth));
+    }
+
+    // Liveness and readiness probes carry no credentials, so these stay
+    // outside the authentication layer.
+    let probes = Router::new()
+        .route("/health", get(health))
+        .route("/ready", get(ready))
+        .with_state(state);
+
+    app.merge(probes)
+}
+
+/// Serves files from the first of `dirs` that has them, so a custom
+/// directory can override single assets of the stock dashboard.
+fn static_files(dirs: &[PathBuf]) -> Option<Router> {
+    let (last, overrides) = dirs.split_last()?;
+    let mut service = Router::new().fallback_service(ServeDir::new(last));
+    for dir in overrides.iter().rev() {
+        service = Router::new().fallback_service(ServeDir::new(dir).fallback(service));
+    }
+    Some(service)
+}
+
+/// Answers unmatched routes when there is no dashboard to fall back to.
+async fn not_found() -> impl IntoResponse {
+    (StatusCode::NOT_FOUND, Json(serde_json::json!({ "error": "not found" })))
+}
+
+/// Filters accepted by `/peers`.
+#[derive(Deserialize, Debug, Default)]
+pub struct PeersQuery {
+    namespace: Option<String>,
+    reachable: Option<bool>,
+    version_ok: Option<bool>,
+    role: Option<PeerRole>,
+    nat_status: Option<NatStatus>,
+    sort: Option<PeersSort>,
+    /// Whether `/peers` includes each peer's `signed_record`.
+    include_record: Option<bool>,
+}
+
+/// Orderings accepted by `/peers?sort=`. Latencies sort fastest first with
+/// unmeasured peers last, `last_seen` sorts most recent first. Without one,
+/// pinned peers come first.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum PeersSort {
+    Peer,
+    Ping,
+    PingEma,
+    LastSeen,
+}
+
+impl PeersQuery {
+    fn matches(&self, stat: &PeerStat) -> bool {
+        self.namespace.as_ref().is_none_or(|namespace| stat.namespaces.iter().any(|ns| ns.namespace == *namespace))
+            && self.reachable.is_none_or(|reachable| stat.reachable == Some(reachable))
+            && self.version_ok.is_none_or(|version_ok| stat.version_ok == version_ok)
+            && self.role.is_none_or(|role| stat.role == role)
+            && self.nat_status.is_none_or(|nat_status| stat.nat_status == nat_status)
+    }
+
+    fn sort(&self, peers: &mut [PeerStat]) {
+        fn by_latency(a: Option<f64>, b: Option<f64>) -> Ordering {
+            match (a, b) {
+                (Some(a), Some(b)) => a.total_cmp(&b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+        }
+
+        match self.sort {
+            None => peers.sort_by_key(|stat| !stat.pinned),
+            Some(PeersSort::Peer) => peers.sort_by(|a, b| a.peer.cmp(&b.peer)),
+            Some(PeersSort::Ping) => peers.sort_by(|a, b| by_latency(a.ping.map(|p| p as f64), b.ping.map(|p| p as f64))),
+            Some(PeersSort::PingEma) => peers.sort_by(|a, b| by_latency(a.ping_ema, b.ping_ema)),
+            Some(PeersSort::LastSeen) => peers.sort_by_key(|stat| std::cmp::Reverse(stat.last_seen)),
+        }
+    }
+}
+
+/// Current peer counts, served at `/stats`.
+#[derive(Serialize, Debug, Default)]
+struct PeerCounts {
+    total: usize,
+    reachable: usize,
+    unreachable: usize,
+    /// Registered peers by namespace.
+    namespaces: BTreeMap<String, usize>,
+    /// Distinct IPs among the peers' advertised addresses.
+    distinct_ips: usize,
+    /// Most peers advertising the same IP; a sudden rise hints at many
+    /// identities run from one host.
+    max_peers_per_ip: usize,
+    /// Discovers answered without registrations, since startup.
+    empty_discovers: u64,
+    /// The enquirers getting the most empty responses, often clients asking
+    /// for a namespace nobody registers under.
+    top_empty_enquirers: Vec<EnquirerStat>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TopQuery {
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RecordQuery {
+    include_record: Option<bool>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ExpiringQuery {
+    within_secs: u64,
+}
+
+async fn peers(State(state): State<AppState>, headers: HeaderMap, Query(query): Query<PeersQuery>) -> Response {
+    let mut peers = state.peers.lock().unwrap().values()
+        .filter(|stat| query.matches(stat))
+        .map(|stat| stat.clone().with_record(query.include_record.unwrap_or(false)))
+        .collect::<Vec<_>>();
+    query.sort(&mut peers);
+    negotiated(&headers, &peers)
+}
+
+/// The `/peers` list grouped by namespace, taking the same parameters. A
+/// peer is listed under each of its namespaces, and with `namespace=` only
+/// that group is returned.
+async fn peers_by_namespace(State(state): State<AppState>, headers: HeaderMap, Query(query): Query<PeersQuery>) -> Response {
+    let mut groups = BTreeMap::<String, Vec<PeerStat>>::new();
+    for stat in state.peers.lock().unwrap().values().filter(|stat| query.matches(stat)) {
+        let stat = stat.clone().with_record(query.include_record.unwrap_or(false));
+        for ns in &stat.namespaces {
+            if query.namespace.as_ref().is_none_or(|namespace| ns.namespace == *namespace) {
+                groups.entry(ns.namespace.clone()).or_default().push(stat.clone());
+            }
+        }
+    }
+    for peers in groups.values_mut() {
+        query.sort(peers);
+    }
+    negotiated(&headers, &groups)
+}
+
+/// Only the PeerIds, sorted, for clients that diff against their own peerstore.
+async fn peer_ids(State(state): State<AppState>, Query(query): Query<PeersQuery>) -> Json<Vec<String>> {
+    let mut ids = state.peers.lock().unwrap().values()
+        .filter(|stat| query.matches(stat))
+        .map(|stat| stat.peer.clone())
+        .collect::<Vec<_>>();
+    ids.sort();
+    Json(ids)
+}
+
+/// Shaped for js-libp2p's peerStore `merge`/`patch`: PeerId -> multiaddrs.
+async fn peers_libp2p(State(state): State<AppState>, Query(query): Query<PeersQuery>) -> Json<BTreeMap<String, Vec<String>>> {
+    let peers = state.peers.lock().unwrap().values()
+        .filter(|stat| query.matches(stat))
+        .map(|stat| {
+            let addrs = stat.addrinfo.iter().map(|a| a.address.clone()).collect::<Vec<_>>();
+            (stat.peer.clone(), addrs)
+        })
+        .collect();
+    Json(peers)
+}
+
+/// A missing or non-numeric `within_secs` is rejected with 400 by `Query`.
+async fn peers_expiring(State(state): State<AppState>, Query(query): Query<ExpiringQuery>) -> Json<Vec<PeerStat>> {
+    let deadline = now_ms().saturating_add_unsigned(query.within_secs.saturating_mul(1000));
+    let mut peers = state.peers.lock().unwrap().values()
+        .filter(|stat| stat.next_expiry().is_some_and(|expiry| expiry <= deadline))
+        .map(|stat| stat.clone().with_record(false))
+        .collect::<Vec<_>>();
+    peers.sort_by_key(|stat| stat.next_expiry());
+    Json(peers)
+}
+
+async fn peer(State(state): State<AppState>, Path(id): Path<String>, Query(query): Query<RecordQuery>) -> Response {
+    let Ok(peer) = id.parse::<PeerId>() else {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": "invalid peer id" }))).into_response();
+    };
+    match state.peers.lock().unwrap().get(&peer) {
+        Some(stat) => Json(stat.clone().with_record(query.include_record.unwrap_or(false))).into_response(),
+        None => (StatusCode::NOT_FOUND, Json(serde_json::json!({ "error": "unknown peer" }))).into_response(),
+    }
+}
+
+async fn stats(State(state): State<AppState>) -> Json<PeerCounts> {
+    let peers = state.peers.lock().unwrap();
+    let mut counts = PeerCounts {
+        total: peers.len(),
+        ..Default::default()
+    };
+    for stat in peers.values() {
+        match stat.reachable {
+            Some(true) => counts.reachable += 1,
+            Some(false) => counts.unreachable += 1,
+            None => {}
+        }
+        for ns in &stat.namespaces {
+            *counts.namespaces.entry(ns.namespace.clone()).or_default() += 1;
+        }
+    }
+
+    let mut peers_per_ip = HashMap::<IpAddr, usize>::new();
+    for stat in peers.values() {
+        let ips = stat.addrinfo.iter()
+            .filter_map(|addr| addr.address.parse::<Multiaddr>().ok())
+            .filter_map(|addr| match addr.iter().next() {
+                Some(Protocol::Ip4(ip)) => Some(IpAddr::from(ip)),
+                Some(Protocol::Ip6(ip)) => Some(IpAddr::from(ip)),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+        for ip in ips {
+            *peers_per_ip.entry(ip).or_default() += 1;
+        }
+    }
+    counts.distinct_ips = peers_per_ip.len();
+    counts.max_peers_per_ip = peers_per_ip.values().copied().max().unwrap_or(0);
+    drop(peers);
+
+    counts.empty_discovers = state.metrics.discovers.lock().unwrap().get("").copied().unwrap_or(0);
+    counts.top_empty_enquirers = state.enquirers.lock().unwrap().top_empty(10, now_ms());
+
+    Json(counts)
+}
+
+async fn stats_history(State(state): State<AppState>) -> Json<History> {
+    Json(state.history.lock().unwrap().clone())
+}
+
+async fn stats_enquirers(State(state): State<AppState>, Query(query): Query<TopQuery>) -> impl IntoResponse {
+    let top = state.enquirers.lock().unwrap().top(query.limit.unwrap_or(10), now_ms());
+    Json(top)
+}
+
+async fn info(State(state): State<AppState>) -> Json<NodeInfo> {
+    Json(state.node_info.lock().unwrap().clone())
+}
+
+/// Where clients should dial this server, preferred address first.
+async fn bootstrap(State(state): State<AppState>) -> Json<serde_json::Value> {
+    let node_info = state.node_info.lock().unwrap();
+    Json(serde_json::json!({
+        "peer_id": node_info.peer_id,
+        "addresses": node_info.bootstrap_addresses(),
+    }))
+}
+
+async fn ready(State(state): State<AppState>) -> impl IntoResponse {
+    let ready = state.node_info.lock().unwrap().listening_ready;
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(serde_json::json!({ "ready": ready })))
+}
+
+async fn metrics(State(state): State<AppState>) -> String {
+    state.metrics.render()
+}
+
+async fn connections(State(state): State<AppState>) -> Json<Vec<ConnectedPeer>> {
+    // Take the peer table lock once, without holding it across the other.
+    let registered = state.peers.lock().unwrap().keys().copied().collect::<HashSet<_>>();
+    Json(state.connections.lock().unwrap().list(|peer| registered.contains(peer)))
+}
+
+async fn connections_per_ip(State(state): State<AppState>) -> Json<BTreeMap<IpAddr, usize>> {
+    Json(state.ip_counts.lock().unwrap().clone())
+}
+
+/// Liveness, or a composite check when `BOTUN_AURA_HEALTH_*` thresholds are
+/// set: any failing signal makes the status `degraded`.
+async fn health(State(state): State<AppState>) -> impl IntoResponse {
+    let (connected_peers, ever_connected) = {
+        let connections = state.connections.lock().unwrap();
+        (connections.len(), connections.ever_connected())
+    };
+    let signals = Signals {
+        loop_stall_ms: now_ms() - state.loop_heartbeat.load(atomic::Ordering::Relaxed),
+        listening_ready: state.node_info.lock().unwrap().listening_ready,
+        event_queue_depth: state.metrics.event_queue_depth(),
+        connected_peers,
+        ever_connected,
+    };
+    let failing = state.health.failing(&signals);
+
+    let mut health = serde_json::json!({ "status": "ok" });
+    let mut status = StatusCode::OK;
+    if !failing.is_empty() {
+        health = serde_json::json!({ "status": "degraded", "failing": failing });
+        status = state.health.degraded_status;
+    }
+    if let Some(self_check) = &state.self_check {
+        health["self_check"] = serde_json::to_value(&*self_check.lock().unwrap()).unwrap();
+    }
+    (status, Json(health))
+}
+
+async fn deny_peer(State(state): State<AppState>, Path(id): Path<String>) -> impl IntoResponse {
+    let Ok(peer) = id.parse::<PeerId>() else {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": "invalid peer id" })));
+    };
+    let (reply, outcome) = oneshot::channel();
+    if state.commands.send(Command::Deny { peer, reply }).await.is_err() {
+        return (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({ "error": "shutting down" })));
+    }
+    match outcome.await {
+        Ok(outcome) => (StatusCode::OK, Json(serde_json::to_value(outcome).unwrap())),
+        Err(_) => (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({ "error": "shutting down" }))),
+    }
+}
+
+async fn disconnect_peer(State(state): State<AppState>, Path(id): Path<String>) -> impl IntoResponse {
+    let Ok(peer) = id.parse::<PeerId>() else {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": "invalid peer id" })));
+    };
+    let (reply, outcome) = oneshot::channel();
+    if state.commands.send(Command::Disconnect { peer, reply }).await.is_err() {
+        return (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({ "error": "shutting down" })));
+    }
+    match outcome.await {
+        Ok(true) => (StatusCode::OK, Json(serde_json::json!({ "peer": peer.to_string(), "disconnected": true }))),
+        Ok(false) => (StatusCode::NOT_FOUND, Json(serde_json::json!({ "error": "peer not connected" }))),
+        Err(_) => (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({ "error": "shutting down" }))),
+    }
+}
+
+async fn export(State(state): State<AppState>) -> Json<Export> {
+    let peers = state.peers.lock().unwrap().values().cloned().collect();
+    Json(Export { exported_at: now_ms(), peers })
+}
+
+/// Takes the body as bytes so malformed JSON gets the same error format as
+/// invalid entries.
+async fn import(State(state): State<AppState>, body: Bytes) -> impl IntoResponse {
+    let peers = match serde_json::from_slice::<Import>(&body).map_err(|e| e.to_string()).and_then(Import::validate) {
+        Ok(peers) => peers,
+        Err(e) => return (StatusCode::UNPROCESSABLE_ENTITY, Json(serde_json::json!({ "error": e }))),
+    };
+    let (reply, outcome) = oneshot::channel();
+    if state.commands.send(Command::Import { peers, reply }).await.is_err() {
+        return (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({ "error": "shutting down" })));
+    }
+    match outcome.await {
+        Ok(outcome) => (StatusCode::OK, Json(serde_json::to_value(outcome).unwrap())),
+        Err(_) => (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({ "error": "shutting down" }))),
+    }
+}
+
+async fn reload(State(state): State<AppState>) -> impl IntoResponse {
+    let (reply, outcome) = oneshot::channel();
+    if state.commands.send(Command::Reload { reply }).await.is_err() {
+        return (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({ "error": "shutting down" })));
+    }
+    match outcome.await {
+        Ok(Ok(outcome)) => (StatusCode::OK, Json(serde_json::to_value(outcome).unwrap())),
+        Ok(Err(e)) => (StatusCode::UNPROCESSABLE_ENTITY, Json(serde_json::json!({ "error": e }))),
+        Err(_) => (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({ "error": "shutting down" }))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::{header, Request}};
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use libp2p::rendezvous::Namespace;
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{AddrInfo, ReachableVia, SwarmOptions};
+
+    struct Fixture {
+        router: Router,
+        fast: PeerId,
+        slow: PeerId,
+        unprobed: PeerId,
+        loop_heartbeat: Arc<AtomicI64>,
+    }
+
+    fn peer_stat(peer: PeerId, ip: &str, namespaces: &[&'static str], ping: Option<u64>) -> PeerStat {
+        let now = now_ms();
+        let mut stat = PeerStat::new(peer, now);
+        stat.set_addresses(vec![
+            AddrInfo { address: format!("/ip4/{ip}/tcp/4001/p2p/{peer}") },
+            AddrInfo { address: format!("/ip4/{ip}/udp/4001/quic-v1/p2p/{peer}") },
+        ]);
+        for namespace in namespaces {
+            stat.register(&Namespace::from_static(namespace), 7200, now);
+        }
+        if let Some(ping) = ping {
+            stat.record_ping(ping, 0.2);
+            stat.set_reachable(true, ReachableVia::Ping);
+        }
+        stat.signed_record = Some("c2lnbmVk".to_string());
+        stat
+    }
+
+    fn fixture() -> Fixture {
+        fixture_with(vec!["does-not-exist".into()], |_| {})
+    }
+
+    fn fixture_with(static_dirs: Vec<PathBuf>, configure: impl FnOnce(&mut AppState)) -> Fixture {
+        let (fast, slow, unprobed) = (PeerId::random(), PeerId::random(), PeerId::random());
+        let peers = HashMap::from([
+            (fast, peer_stat(fast, "192.0.2.1", &["chat", "files"], Some(5))),
+            (slow, peer_stat(slow, "192.0.2.1", &["chat"], Some(250))),
+            (unprobed, peer_stat(unprobed, "198.51.100.7", &["files"], None)),
+        ]);
+
+        let (commands, _) = mpsc::channel(1);
+        let loop_heartbeat = Arc::new(AtomicI64::new(now_ms()));
+        let mut state = AppState {
+            peers: Arc::new(Mutex::new(peers)),
+            history: Arc::new(Mutex::new(History::from_env())),
+            enquirers: Arc::new(Mutex::new(Enquirers::from_env())),
+            node_info: Arc::new(Mutex::new(NodeInfo::new(
+                PeerId::random(),
+                SwarmOptions::default().capabilities(),
+                [],
+            ))),
+            metrics: Arc::new(Metrics::default()),
+            commands,
+            basic_auth: None,
+            self_check: None,
+            ip_counts: IpCounts::default(),
+            connections: Arc::default(),
+            health: Arc::default(),
+            loop_heartbeat: loop_heartbeat.clone(),
+            static_dirs,
+        };
+        configure(&mut state);
+
+        Fixture {
+            router: build_router(state),
+            fast,
+            slow,
+            unprobed,
+            loop_heartbeat,
+        }
+    }
+
+    async fn get(router: &Router, uri: &str) -> (StatusCode, Vec<u8>) {
+        let request = Request::get(uri).body(Body::empty()).unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, body.to_vec())
+    }
+
+    async fn get_json(router: &Router, uri: &str) -> (StatusCode, Value) {
+        let (status, body) = get(router, uri).await;
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    fn peer_ids(peers: &Value) -> Vec<String> {
+        peers.as_array().unwrap().iter().map(|p| p["peer"].as_str().unwrap().to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn peers_lists_every_peer() {
+        let f = fixture();
+
+        let (status, peers) = get_json(&f.router, "/peers").await;
+
+        assert_eq!(status, StatusCode::OK);
+        let mut ids = peer_ids(&peers);
+        ids.sort();
+        let mut expected = vec![f.fast.to_string(), f.slow.to_string(), f.unprobed.to_string()];
+        expected.sort();
+        assert_eq!(ids, expected);
+    }
+
+    #[tokio::test]
+    async fn peers_filters_by_namespace() {
+        let f = fixture();
+
+        let (status, peers) = get_json(&f.router, "/peers?namespace=chat&sort=peer").await;
+
+        assert_eq!(status, StatusCode::OK);
+        let mut expected = vec![f.fast.to_string(), f.slow.to_string()];
+        expected.sort();
+        assert_eq!(peer_ids(&peers), expected);
+
+        let (_, peers) = get_json(&f.router, "/peers?namespace=nobody").await;
+        assert_eq!(peers, serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn peers_grouped_by_namespace() {
+        let f = fixture();
+
+        let (status, groups) = get_json(&f.router, "/peers/by-namespace?sort=ping").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(groups.as_object().unwrap().keys().collect::<Vec<_>>(), ["chat", "files"]);
+        assert_eq!(peer_ids(&groups["chat"]), [f.fast.to_string(), f.slow.to_string()]);
+        assert_eq!(peer_ids(&groups["files"]), [f.fast.to_string(), f.unprobed.to_string()]);
+
+        let (_, groups) = get_json(&f.router, "/peers/by-namespace?namespace=files").await;
+        assert_eq!(groups.as_object().unwrap().keys().collect::<Vec<_>>(), ["files"]);
+
+        let (_, groups) = get_json(&f.router, "/peers/by-namespace?namespace=nobody").await;
+        assert_eq!(groups, serde_json::json!({}));
+    }
+
+    #[tokio::test]
+    async fn peer_ids_are_sorted_and_filtered() {
+        let f = fixture();
+
+        let (_, ids) = get_json(&f.router, "/peers/ids").await;
+        let mut expected = vec![f.fast.to_string(), f.slow.to_string(), f.unprobed.to_string()];
+        expected.sort();
+        assert_eq!(ids, serde_json::json!(expected));
+
+        let (_, ids) = get_json(&f.router, "/peers/ids?namespace=files").await;
+        let mut expected = vec![f.fast.to_string(), f.unprobed.to_string()];
+        expected.sort();
+        assert_eq!(ids, serde_json::json!(expected));
+    }
+
+    #[tokio::test]
+    async fn peers_filters_by_reachability() {
+        let f = fixture();
+
+        let (_, peers) = get_json(&f.router, "/peers?reachable=true&sort=peer").await;
+
+        let mut expected = vec![f.fast.to_string(), f.slow.to_string()];
+        expected.sort();
+        assert_eq!(peer_ids(&peers), expected);
+    }
+
+    #[tokio::test]
+    async fn peers_filters_by_nat_status() {
+        let f = fixture();
+
+        let (_, peers) = get_json(&f.router, "/peers?nat_status=unknown").await;
+        assert_eq!(peers.as_array().unwrap().len(), 3);
+
+        let (_, peers) = get_json(&f.router, "/peers?nat_status=behind_nat").await;
+        assert_eq!(peers, serde_json::json!([]));
+
+        let (status, _) = get(&f.router, "/peers?nat_status=closed").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn peers_sorts_by_ping_with_unprobed_last() {
+        let f = fixture();
+
+        let (status, peers) = get_json(&f.router, "/peers?sort=ping").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(peer_ids(&peers), vec![f.fast.to_string(), f.slow.to_string(), f.unprobed.to_string()]);
+        assert_eq!(peers[0]["ping"], 5);
+        assert_eq!(peers[2]["ping"], Value::Null);
+    }
+
+    #[tokio::test]
+    async fn peers_rejects_bad_query_parameters() {
+        let f = fixture();
+
+        let (status, _) = get(&f.router, "/peers?sort=fastest").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = get(&f.router, "/peers?reachable=maybe").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn peer_by_id() {
+        let f = fixture();
+
+        let (status, peer) = get_json(&f.router, &format!("/peers/{}", f.slow)).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(peer["peer"], f.slow.to_string());
+        assert_eq!(peer["ping"], 250);
+        assert_eq!(peer["reachable"], true);
+        assert_eq!(peer["reachable_via"], "ping");
+        assert_eq!(peer["namespaces"][0]["namespace"], "chat");
+        assert_eq!(peer["namespaces"][0]["ttl"], 7200);
+    }
+
+    #[tokio::test]
+    async fn signed_records_are_opt_in() {
+        let f = fixture();
+
+        let (_, peers) = get_json(&f.router, "/peers").await;
+        assert!(peers.as_array().unwrap().iter().all(|p| p.get("signed_record").is_none()));
+
+        let (_, peers) = get_json(&f.router, "/peers?include_record=true").await;
+        assert!(peers.as_array().unwrap().iter().all(|p| p["signed_record"] == "c2lnbmVk"));
+
+        let (_, peer) = get_json(&f.router, &format!("/peers/{}?include_record=true", f.fast)).await;
+        assert_eq!(peer["signed_record"], "c2lnbmVk");
+    }
+
+    #[tokio::test]
+    async fn peer_by_id_errors() {
+        let f = fixture();
+
+        let (status, body) = get_json(&f.router, &format!("/peers/{}", PeerId::random())).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error"], "unknown peer");
+
+        let (status, body) = get_json(&f.router, "/peers/not-a-peer-id").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "invalid peer id");
+    }
+
+    #[tokio::test]
+    async fn fixed_peer_routes_win_over_peer_ids() {
+        let f = fixture();
+
+        let (status, peers) = get_json(&f.router, "/peers/libp2p").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(peers.as_object().unwrap().contains_key(&f.fast.to_string()));
+    }
+
+    #[tokio::test]
+    async fn expiring_requires_a_window() {
+        let f = fixture();
+
+        let (status, _) = get(&f.router, "/peers/expiring").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = get(&f.router, "/peers/expiring?within_secs=soon").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, peers) = get_json(&f.router, "/peers/expiring?within_secs=60").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(peers, serde_json::json!([]));
+
+        let (_, peers) = get_json(&f.router, "/peers/expiring?within_secs=7200").await;
+        assert_eq!(peers.as_array().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn stats_counts_peers() {
+        let f = fixture();
+
+        let (status, stats) = get_json(&f.router, "/stats").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            stats,
+            serde_json::json!({
+                "total": 3,
+                "reachable": 2,
+                "unreachable": 0,
+                "namespaces": { "chat": 2, "files": 2 },
+                "distinct_ips": 2,
+                "max_peers_per_ip": 2,
+                "empty_discovers": 0,
+                "top_empty_enquirers": [],
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn health_is_ok() {
+        let f = fixture();
+
+        let (status, body) = get_json(&f.router, "/health").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, serde_json::json!({ "status": "ok" }));
+    }
+
+    #[tokio::test]
+    async fn health_reports_failing_signals() {
+        let f = fixture_with(vec!["does-not-exist".into()], |state| {
+            state.health = Arc::new(HealthChecks {
+                max_loop_stall_ms: Some(30_000),
+                degraded_status: StatusCode::SERVICE_UNAVAILABLE,
+                ..HealthChecks::default()
+            });
+        });
+        assert_eq!(get_json(&f.router, "/health").await.0, StatusCode::OK);
+
+        f.loop_heartbeat.store(now_ms() - 60_000, atomic::Ordering::Relaxed);
+        let (status, body) = get_json(&f.router, "/health").await;
+
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "degraded");
+        assert_eq!(body["failing"][0]["signal"], "loop_stalled");
+    }
+
+    #[tokio::test]
+    async fn probes_need_no_authentication() {
+        let f = fixture_with(vec!["does-not-exist".into()], |state| {
+            state.basic_auth = Some(Arc::new(BasicAuth::new("admin", "secret")));
+        });
+
+        assert_eq!(get(&f.router, "/health").await.0, StatusCode::OK);
+        assert_eq!(get(&f.router, "/ready").await.0, StatusCode::OK);
+        assert_eq!(get(&f.router, "/peers").await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(get(&f.router, "/metrics").await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(get(&f.router, "/index.html").await.0, StatusCode::UNAUTHORIZED);
+
+        let request = Request::get("/peers")
+            .header(header::AUTHORIZATION, format!("Basic {}", STANDARD.encode("admin:secret")))
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(f.router.clone().oneshot(request).await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn unknown_routes_are_not_found() {
+        let f = fixture();
+
+        let (status, _) = get(&f.router, "/no/such/route").await;
+
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn api_only_answers_unknown_routes_with_json() {
+        let f = fixture_with(vec![], |_| {});
+
+        let (status, body) = get_json(&f.router, "/index.html").await;
+
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body, serde_json::json!({ "error": "not found" }));
+    }
+
+    #[tokio::test]
+    async fn static_dirs_fall_through_in_order() {
+        let root = std::env::temp_dir().join(format!("botun-aura-static-{}", PeerId::random()));
+        let (custom, stock) = (root.join("custom"), root.join("stock"));
+        for (dir, files) in [(&custom, &["app.css"][..]), (&stock, &["app.css", "index.html"][..])] {
+            std::fs::create_dir_all(dir).unwrap();
+            for file in files {
+                std::fs::write(dir.join(file), format!("{} {file}", dir.file_name().unwrap().to_str().unwrap())).unwrap();
+            }
+        }
+        let f = fixture_with(vec![custom, stock], |_| {});
+
+        let css = get(&f.router, "/app.css").await;
+        let index = get(&f.router, "/index.html").await;
+        let missing = get(&f.router, "/missing.js").await;
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(css, (StatusCode::OK, b"custom app.css".to_vec()));
+        assert_eq!(index, (StatusCode::OK, b"stock index.html".to_vec()));
+        assert_eq!(missing.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn admin_routes_need_authentication_configured() {
+        let f = fixture();
+
+        let request = Request::post(format!("/admin/peers/{}/deny", f.slow)).body(Body::empty()).unwrap();
+        let response = f.router.clone().oneshot(request).await.unwrap();
+
+        // Falls through to the static files, which only serve GET and HEAD.
+        assert!(response.status().is_client_error());
+    }
+}
@@ -0,0 +1,87 @@
+use std::{env, sync::Arc};
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+/// Credentials for HTTP Basic authentication, from `BOTUN_AURA_BASIC_AUTH=user:pass`.
+#[derive(Debug)]
+pub struct BasicAuth {
+    user: String,
+    password: String,
+}
+
+impl BasicAuth {
+    pub fn from_env() -> Option<Arc<Self>> {
+        let value = env::var("BOTUN_AURA_BASIC_AUTH").ok()?;
+        let (user, password) = value
+            .split_once(':')
+            .expect("BOTUN_AURA_BASIC_AUTH must be in the form user:pass");
+
+        Some(Arc::new(BasicAuth::new(user, password)))
+    }
+
+    pub fn new(user: &str, password: &str) -> Self {
+        BasicAuth {
+            user: user.to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    fn accepts(&self, authorization: &HeaderValue) -> bool {
+        let Some(encoded) = authorization
+            .to_str()
+            .ok()
+            .and_then(|value| value.strip_prefix("Basic "))
+        else {
+            return false;
+        };
+        let Ok(decoded) = STANDARD.decode(encoded.trim()) else {
+            return false;
+        };
+        let Some(colon) = decoded.iter().position(|&b| b == b':') else {
+            return false;
+        };
+
+        let (user, password) = (&decoded[..colon], &decoded[colon + 1..]);
+        // Evaluate both comparisons so the response time does not reveal
+        // which half was wrong.
+        let user_ok = constant_time_eq(user, self.user.as_bytes());
+        let password_ok = constant_time_eq(password, self.password.as_bytes());
+        user_ok & password_ok
+    }
+}
+
+/// Compares two byte strings in time that depends only on their lengths.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Rejects requests without valid Basic credentials, prompting browsers for a login.
+pub async fn require_basic_auth(
+    State(auth): State<Arc<BasicAuth>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .is_some_and(|value| auth.accepts(value));
+
+    if authorized {
+        next.run(request).await
+    } else {
+        (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Basic realm=\"botun-aura\", charset=\"UTF-8\"")],
+        )
+            .into_response()
+    }
+}
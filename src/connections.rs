@@ -0,0 +1,104 @@
+use std::collections::BTreeMap;
+
+use libp2p::PeerId;
+use serde::Serialize;
+
+/// One connected peer as shown by `/connections`, registered or not.
+#[derive(Serialize, Debug, Clone)]
+pub struct ConnectedPeer {
+    pub peer: String,
+    pub connections: u32,
+    pub connected_since: i64,
+    /// Last ping round trip in milliseconds, `None` until the first ping.
+    pub ping: Option<u64>,
+    /// Whether the peer is in the registered peer table.
+    pub registered: bool,
+}
+
+/// Every peer with an open connection, kept in step with the swarm's
+/// connection events.
+#[derive(Debug, Default)]
+pub struct Connections {
+    peers: BTreeMap<PeerId, Entry>,
+    ever_connected: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    connections: u32,
+    connected_since: i64,
+    ping: Option<u64>,
+}
+
+impl Connections {
+    pub fn established(&mut self, peer: PeerId, num_established: u32, now: i64) {
+        self.ever_connected = true;
+        self.peers
+            .entry(peer)
+            .or_insert(Entry { connections: 0, connected_since: now, ping: None })
+            .connections = num_established;
+    }
+
+    pub fn closed(&mut self, peer: &PeerId, num_established: u32) {
+        if num_established == 0 {
+            self.peers.remove(peer);
+        } else if let Some(entry) = self.peers.get_mut(peer) {
+            entry.connections = num_established;
+        }
+    }
+
+    pub fn record_ping(&mut self, peer: &PeerId, rtt_ms: u64) {
+        if let Some(entry) = self.peers.get_mut(peer) {
+            entry.ping = Some(rtt_ms);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    /// Whether any peer has connected since startup.
+    pub fn ever_connected(&self) -> bool {
+        self.ever_connected
+    }
+
+    /// The connected peers sorted by PeerId, `registered` as told by `is_registered`.
+    pub fn list(&self, is_registered: impl Fn(&PeerId) -> bool) -> Vec<ConnectedPeer> {
+        self.peers
+            .iter()
+            .map(|(peer, entry)| ConnectedPeer {
+                peer: peer.to_string(),
+                connections: entry.connections,
+                connected_since: entry.connected_since,
+                ping: entry.ping,
+                registered: is_registered(peer),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_connection_events() {
+        let (peer, other) = (PeerId::random(), PeerId::random());
+        let mut connections = Connections::default();
+
+        connections.established(peer, 1, 1_000);
+        connections.established(peer, 2, 2_000);
+        connections.record_ping(&peer, 12);
+        // Pings for peers that are not connected are ignored.
+        connections.record_ping(&other, 5);
+        connections.closed(&peer, 1);
+
+        let list = connections.list(|_| false);
+        assert_eq!(list.len(), 1);
+        assert_eq!((list[0].connections, list[0].connected_since, list[0].ping), (1, 1_000, Some(12)));
+
+        connections.closed(&peer, 0);
+        assert!(connections.list(|_| false).is_empty());
+        assert_eq!((connections.len(), connections.ever_connected()), (0, true));
+    }
+}
@@ -0,0 +1,180 @@
+use std::{collections::HashSet, env};
+
+use libp2p::{multiaddr::Protocol, Multiaddr};
+
+use crate::env_flag;
+
+/// Which address family the probe tick tries first, from
+/// `BOTUN_AURA_DIAL_PREFER=ipv6|ipv4|none`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DialPreference {
+    Ipv6,
+    Ipv4,
+    #[default]
+    None,
+}
+
+impl DialPreference {
+    pub fn from_env() -> Self {
+        match env::var("BOTUN_AURA_DIAL_PREFER").as_deref().map(str::trim) {
+            Ok("ipv6") => DialPreference::Ipv6,
+            Ok("ipv4") => DialPreference::Ipv4,
+            Ok("none") | Ok("") | Err(_) => DialPreference::None,
+            Ok(other) => {
+                tracing::warn!("Ignoring BOTUN_AURA_DIAL_PREFER={other}, expected ipv6, ipv4 or none");
+                DialPreference::None
+            }
+        }
+    }
+
+    /// Moves addresses of the preferred family to the front, keeping the
+    /// stored order otherwise.
+    fn order(self, addresses: &mut [Multiaddr]) {
+        let preferred: fn(&Multiaddr) -> bool = match self {
+            DialPreference::Ipv6 => is_ipv6,
+            DialPreference::Ipv4 => is_ipv4,
+            DialPreference::None => return,
+        };
+        addresses.sort_by_key(|address| !preferred(address));
+    }
+}
+
+/// Which peers the probe tick dials, at which of their addresses and in
+/// which order.
+#[derive(Debug, Clone, Default)]
+pub struct DialPolicy {
+    pub prefer: DialPreference,
+    /// From `BOTUN_AURA_NO_IPV6_DIAL=1`, for hosts with broken IPv6 routing.
+    /// Listening on IPv6 is not affected.
+    pub skip_ipv6: bool,
+    /// From `BOTUN_AURA_NO_PROBE_NAMESPACES`, comma separated namespaces whose
+    /// peers are known to be unreachable, e.g. NATed clients.
+    pub no_probe_namespaces: HashSet<String>,
+}
+
+impl DialPolicy {
+    pub fn from_env() -> Self {
+        DialPolicy {
+            prefer: DialPreference::from_env(),
+            skip_ipv6: env_flag("BOTUN_AURA_NO_IPV6_DIAL"),
+            no_probe_namespaces: env::var("BOTUN_AURA_NO_PROBE_NAMESPACES")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(String::from)
+                .collect(),
+        }
+    }
+
+    /// Whether a peer registered under `namespaces` is probed: unless every
+    /// one of them is a no-probe namespace. Peers without registrations,
+    /// i.e. pinned ones, are always probed.
+    pub fn probes<'a>(&self, namespaces: impl IntoIterator<Item = &'a str>) -> bool {
+        let mut namespaces = namespaces.into_iter().peekable();
+        namespaces.peek().is_none() || namespaces.any(|namespace| !self.no_probe_namespaces.contains(namespace))
+    }
+
+    /// The addresses to dial, in the order to try them.
+    pub fn plan(&self, addresses: impl IntoIterator<Item = Multiaddr>) -> Vec<Multiaddr> {
+        let mut addresses = addresses
+            .into_iter()
+            .filter(|address| !(self.skip_ipv6 && is_ipv6(address)))
+            .collect::<Vec<_>>();
+        self.prefer.order(&mut addresses);
+        addresses
+    }
+}
+
+fn is_ipv4(address: &Multiaddr) -> bool {
+    matches!(address.iter().next(), Some(Protocol::Ip4(_) | Protocol::Dns4(_)))
+}
+
+fn is_ipv6(address: &Multiaddr) -> bool {
+    matches!(address.iter().next(), Some(Protocol::Ip6(_) | Protocol::Dns6(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addresses() -> Vec<Multiaddr> {
+        [
+            "/ip6/2001:db8::1/tcp/4001",
+            "/ip4/192.0.2.1/tcp/4001",
+            "/dns6/peer.example/tcp/4001",
+            "/dns4/peer.example/tcp/4001",
+            "/ip4/192.0.2.1/tcp/4001/p2p/12D3KooWDpJ7As7BWAwRMfu1VU2WCqNjvq387JEYKDBj4kx6nXTN/p2p-circuit",
+        ]
+        .into_iter()
+        .map(|a| a.parse().unwrap())
+        .collect()
+    }
+
+    fn strings(addresses: Vec<Multiaddr>) -> Vec<String> {
+        addresses.into_iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn keeps_stored_order_by_default() {
+        assert_eq!(DialPolicy::default().plan(addresses()), addresses());
+    }
+
+    #[test]
+    fn no_ipv6_dial_skips_ipv6_addresses() {
+        let policy = DialPolicy {
+            skip_ipv6: true,
+            ..Default::default()
+        };
+
+        let planned = strings(policy.plan(addresses()));
+
+        assert_eq!(
+            planned,
+            vec![
+                "/ip4/192.0.2.1/tcp/4001",
+                "/dns4/peer.example/tcp/4001",
+                "/ip4/192.0.2.1/tcp/4001/p2p/12D3KooWDpJ7As7BWAwRMfu1VU2WCqNjvq387JEYKDBj4kx6nXTN/p2p-circuit",
+            ]
+        );
+    }
+
+    #[test]
+    fn no_ipv6_dial_wins_over_preferring_ipv6() {
+        let policy = DialPolicy {
+            prefer: DialPreference::Ipv6,
+            skip_ipv6: true,
+            ..Default::default()
+        };
+
+        assert!(policy.plan(addresses()).iter().all(|a| !is_ipv6(a)));
+    }
+
+    #[test]
+    fn preferred_family_goes_first() {
+        let policy = DialPolicy {
+            prefer: DialPreference::Ipv6,
+            skip_ipv6: false,
+            ..Default::default()
+        };
+
+        let planned = strings(policy.plan(addresses()));
+
+        assert_eq!(planned[..2], ["/ip6/2001:db8::1/tcp/4001", "/dns6/peer.example/tcp/4001"]);
+        assert_eq!(planned[2], "/ip4/192.0.2.1/tcp/4001");
+    }
+
+    #[test]
+    fn no_probe_namespaces_skip_peers_only_registered_there() {
+        let policy = DialPolicy {
+            no_probe_namespaces: HashSet::from(["clients".to_string(), "mobile".to_string()]),
+            ..Default::default()
+        };
+
+        assert!(!policy.probes(["clients"]));
+        assert!(!policy.probes(["clients", "mobile"]));
+        assert!(policy.probes(["clients", "servers"]));
+        assert!(policy.probes([]));
+        assert!(DialPolicy::default().probes(["clients"]));
+    }
+}
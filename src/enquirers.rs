@@ -0,0 +1,110 @@
+use std::{collections::HashMap, env};
+
+use libp2p::PeerId;
+use serde::Serialize;
+
+const DEFAULT_CAPACITY: usize = 1024;
+
+/// Discover requests served to one enquirer.
+#[derive(Serialize, Debug, Clone)]
+pub struct EnquirerStat {
+    pub enquirer: String,
+    pub discovers: u64,
+    /// Discovers answered without any registration.
+    pub empty_discovers: u64,
+    /// Discovers per minute since the enquirer was first seen, over at least a minute.
+    pub rate_per_min: f64,
+    pub first_seen: i64,
+    pub last_seen: i64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    discovers: u64,
+    empty_discovers: u64,
+    first_seen: i64,
+    last_seen: i64,
+}
+
+/// Per-enquirer discover counts, kept for at most `BOTUN_AURA_MAX_ENQUIRERS`
+/// peers. When full, the least recently active enquirer is forgotten.
+#[derive(Debug)]
+pub struct Enquirers {
+    entries: HashMap<PeerId, Entry>,
+    capacity: usize,
+}
+
+impl Enquirers {
+    pub fn from_env() -> Self {
+        let capacity = env::var("BOTUN_AURA_MAX_ENQUIRERS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|&n| n > 0)
+            .unwrap_or(DEFAULT_CAPACITY);
+
+        Enquirers {
+            entries: HashMap::new(),
+            capacity,
+        }
+    }
+
+    /// Counts a discover served to `enquirer`, `empty` when it returned no
+    /// registrations.
+    pub fn record(&mut self, enquirer: PeerId, empty: bool, now: i64) {
+        if !self.entries.contains_key(&enquirer) && self.entries.len() >= self.capacity {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_seen)
+                .map(|(peer, _)| *peer);
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        let entry = self.entries.entry(enquirer).or_insert(Entry {
+            discovers: 0,
+            empty_discovers: 0,
+            first_seen: now,
+            last_seen: now,
+        });
+        entry.discovers += 1;
+        entry.empty_discovers += u64::from(empty);
+        entry.last_seen = now;
+    }
+
+    /// The `limit` enquirers with the most discovers, busiest first.
+    pub fn top(&self, limit: usize, now: i64) -> Vec<EnquirerStat> {
+        let mut top = self.stats(now);
+        top.sort_by_key(|stat| std::cmp::Reverse(stat.discovers));
+        top.truncate(limit);
+        top
+    }
+
+    /// The `limit` enquirers that got the most empty responses, leaving out
+    /// those that never got one.
+    pub fn top_empty(&self, limit: usize, now: i64) -> Vec<EnquirerStat> {
+        let mut top = self.stats(now);
+        top.retain(|stat| stat.empty_discovers > 0);
+        top.sort_by_key(|stat| std::cmp::Reverse(stat.empty_discovers));
+        top.truncate(limit);
+        top
+    }
+
+    fn stats(&self, now: i64) -> Vec<EnquirerStat> {
+        self.entries
+            .iter()
+            .map(|(peer, entry)| {
+                let minutes = ((now - entry.first_seen).max(60_000) as f64) / 60_000.0;
+                EnquirerStat {
+                    enquirer: peer.to_string(),
+                    discovers: entry.discovers,
+                    empty_discovers: entry.empty_discovers,
+                    rate_per_min: entry.discovers as f64 / minutes,
+                    first_seen: entry.first_seen,
+                    last_seen: entry.last_seen,
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,130 @@
+use std::{env, sync::{atomic::Ordering, Arc}};
+
+use libp2p::PeerId;
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+use crate::{hook::CommandHook, metrics::Metrics, nats::NatsPublisher};
+
+const DEFAULT_QUEUE_DEPTH: usize = 1024;
+
+/// A change to the registered peer set, handed from the swarm loop to the
+/// side-effects task.
+#[derive(Debug, Clone)]
+pub enum PeerEvent {
+    Registered { peer: PeerId, namespace: String },
+    Unregistered { peer: PeerId, namespace: String },
+    Expired { peer: PeerId, namespace: String },
+    /// Removed by the server, e.g. because the peer was denied.
+    Evicted { peer: PeerId, namespace: String },
+}
+
+impl PeerEvent {
+    /// Short event name, as used in logs and by integrations.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PeerEvent::Registered { .. } => "registered",
+            PeerEvent::Unregistered { .. } => "unregistered",
+            PeerEvent::Expired { .. } => "expired",
+            PeerEvent::Evicted { .. } => "evicted",
+        }
+    }
+
+    pub fn peer(&self) -> &PeerId {
+        match self {
+            PeerEvent::Registered { peer, .. }
+            | PeerEvent::Unregistered { peer, .. }
+            | PeerEvent::Expired { peer, .. }
+            | PeerEvent::Evicted { peer, .. } => peer,
+        }
+    }
+
+    pub fn namespace(&self) -> &str {
+        match self {
+            PeerEvent::Registered { namespace, .. }
+            | PeerEvent::Unregistered { namespace, .. }
+            | PeerEvent::Expired { namespace, .. }
+            | PeerEvent::Evicted { namespace, .. } => namespace,
+        }
+    }
+}
+
+/// Sending half of the bounded peer event queue.
+///
+/// The swarm loop must never wait on I/O, so `publish` never blocks: when the
+/// queue is full the event is dropped and counted instead.
+#[derive(Clone)]
+pub struct EventQueue {
+    tx: mpsc::Sender<PeerEvent>,
+    metrics: Arc<Metrics>,
+}
+
+impl EventQueue {
+    pub fn publish(&self, event: PeerEvent) {
+        match self.tx.try_send(event) {
+            Ok(()) => {}
+            Err(TrySendError::Full(event)) => {
+                tracing::warn!("Event queue is full, dropping {:?}", event);
+                self.metrics.events_dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(TrySendError::Closed(event)) => {
+                tracing::error!("Event queue is closed, dropping {:?}", event);
+                self.metrics.events_dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Spawns the side-effects task and returns the queue feeding it. The queue
+/// depth is taken from `BOTUN_AURA_EVENT_QUEUE_DEPTH`.
+///
+/// The task only fans events out: the hook and the NATS publisher each drain
+/// their own queue on their own task, so a slow or throttled hook never holds
+/// up NATS or the event queue itself.
+pub fn spawn(metrics: Arc<Metrics>) -> EventQueue {
+    let depth = env::var("BOTUN_AURA_EVENT_QUEUE_DEPTH")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .filter(|&depth| depth > 0)
+        .unwrap_or(DEFAULT_QUEUE_DEPTH);
+
+    let (tx, mut rx) = mpsc::channel(depth);
+    let _ = metrics.event_queue.set(tx.downgrade());
+
+    let hook = CommandHook::from_env().map(|hook| spawn_hook(hook, depth));
+    let nats = NatsPublisher::from_env(metrics.clone());
+
+    let task_metrics = metrics.clone();
+    tokio::spawn(async move {
+        while let Some(event) = rx.recv().await {
+            handle(event, hook.as_ref(), nats.as_ref(), &task_metrics);
+        }
+    });
+
+    EventQueue { tx, metrics }
+}
+
+/// Runs the hook for every event sent on the returned queue, one at a time.
+fn spawn_hook(mut hook: CommandHook, depth: usize) -> mpsc::Sender<PeerEvent> {
+    let (tx, mut rx) = mpsc::channel::<PeerEvent>(depth);
+    tokio::spawn(async move {
+        while let Some(event) = rx.recv().await {
+            hook.run(&event).await;
+        }
+    });
+    tx
+}
+
+fn handle(event: PeerEvent, hook: Option<&mpsc::Sender<PeerEvent>>, nats: Option<&NatsPublisher>, metrics: &Metrics) {
+    tracing::debug!(peer = %event.peer(), namespace = event.namespace(), "Peer event: {}", event.kind());
+
+    if let Some(nats) = nats {
+        nats.publish(&event);
+    }
+
+    if let Some(hook) = hook
+        && let Err(e) = hook.try_send(event)
+    {
+        tracing::warn!("Peer event hook is behind, dropping {:?}", e.into_inner());
+        metrics.hook_events_dropped.fetch_add(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,168 @@
+use std::env;
+
+use axum::http::StatusCode;
+use serde::Serialize;
+
+use crate::env_flag;
+
+/// Thresholds that turn `/health` from a liveness probe into a composite
+/// check. Each signal is only checked when its variable is set.
+#[derive(Debug, Default)]
+pub struct HealthChecks {
+    /// `BOTUN_AURA_HEALTH_MAX_LOOP_STALL_SECS`: longest the swarm loop may go
+    /// without finishing an iteration.
+    pub max_loop_stall_ms: Option<i64>,
+    /// `BOTUN_AURA_HEALTH_REQUIRE_LISTENERS=1`: every listener must have
+    /// reported an address.
+    pub require_listeners: bool,
+    /// `BOTUN_AURA_HEALTH_MAX_EVENT_QUEUE`: most peer events that may wait
+    /// for the side-effects task.
+    pub max_event_queue: Option<i64>,
+    /// `BOTUN_AURA_HEALTH_REQUIRE_PEERS=1`: once any peer connected, at least
+    /// one must stay connected.
+    pub require_peers: bool,
+    /// `BOTUN_AURA_HEALTH_DEGRADED_STATUS`: `200` (default) or `503`.
+    pub degraded_status: StatusCode,
+}
+
+/// The current values of the signals, gathered by `/health`.
+#[derive(Debug)]
+pub struct Signals {
+    pub loop_stall_ms: i64,
+    pub listening_ready: bool,
+    pub event_queue_depth: i64,
+    pub connected_peers: usize,
+    pub ever_connected: bool,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct Failing {
+    pub signal: &'static str,
+    pub detail: String,
+}
+
+impl HealthChecks {
+    pub fn from_env() -> Self {
+        let degraded_status = match env::var("BOTUN_AURA_HEALTH_DEGRADED_STATUS").as_deref() {
+            Ok("503") => StatusCode::SERVICE_UNAVAILABLE,
+            Ok("200") | Err(_) => StatusCode::OK,
+            Ok(other) => panic!("BOTUN_AURA_HEALTH_DEGRADED_STATUS must be 200 or 503, got '{other}'"),
+        };
+
+        HealthChecks {
+            max_loop_stall_ms: env::var("BOTUN_AURA_HEALTH_MAX_LOOP_STALL_SECS")
+                .ok()
+                .and_then(|s| s.parse::<i64>().ok())
+                .filter(|&n| n > 0)
+                .map(|secs| secs * 1000),
+            require_listeners: env_flag("BOTUN_AURA_HEALTH_REQUIRE_LISTENERS"),
+            max_event_queue: env::var("BOTUN_AURA_HEALTH_MAX_EVENT_QUEUE").ok().and_then(|s| s.parse().ok()),
+            require_peers: env_flag("BOTUN_AURA_HEALTH_REQUIRE_PEERS"),
+            degraded_status,
+        }
+    }
+
+    /// The configured signals that are outside their thresholds.
+    pub fn failing(&self, signals: &Signals) -> Vec<Failing> {
+        let mut failing = vec![];
+        if let Some(max) = self.max_loop_stall_ms
+            && signals.loop_stall_ms > max
+        {
+            failing.push(Failing {
+                signal: "loop_stalled",
+                detail: format!("no swarm loop iteration for {}ms", signals.loop_stall_ms),
+            });
+        }
+        if self.require_listeners && !signals.listening_ready {
+            failing.push(Failing {
+                signal: "listeners_not_ready",
+                detail: "not every listener has reported an address".to_string(),
+            });
+        }
+        if let Some(max) = self.max_event_queue
+            && signals.event_queue_depth > max
+        {
+            failing.push(Failing {
+                signal: "event_queue_lag",
+                detail: format!("{} peer events waiting, more than {max}", signals.event_queue_depth),
+            });
+        }
+        if self.require_peers && signals.ever_connected && signals.connected_peers == 0 {
+            failing.push(Failing {
+                signal: "no_connected_peers",
+                detail: "every peer has disconnected".to_string(),
+            });
+        }
+        failing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn healthy() -> Signals {
+        Signals {
+            loop_stall_ms: 100,
+            listening_ready: true,
+            event_queue_depth: 0,
+            connected_peers: 3,
+            ever_connected: true,
+        }
+    }
+
+    fn all_checks() -> HealthChecks {
+        HealthChecks {
+            max_loop_stall_ms: Some(30_000),
+            require_listeners: true,
+            max_event_queue: Some(100),
+            require_peers: true,
+            degraded_status: StatusCode::OK,
+        }
+    }
+
+    fn signals(failing: Vec<Failing>) -> Vec<&'static str> {
+        failing.into_iter().map(|f| f.signal).collect()
+    }
+
+    #[test]
+    fn unconfigured_signals_are_not_checked() {
+        let bad = Signals {
+            loop_stall_ms: 600_000,
+            listening_ready: false,
+            event_queue_depth: 5_000,
+            connected_peers: 0,
+            ever_connected: true,
+        };
+
+        assert!(HealthChecks::default().failing(&bad).is_empty());
+        assert!(all_checks().failing(&healthy()).is_empty());
+    }
+
+    #[test]
+    fn reports_each_failing_signal() {
+        let checks = all_checks();
+
+        let stalled = Signals { loop_stall_ms: 31_000, ..healthy() };
+        assert_eq!(signals(checks.failing(&stalled)), ["loop_stalled"]);
+
+        let everything = Signals {
+            loop_stall_ms: 31_000,
+            listening_ready: false,
+            event_queue_depth: 101,
+            connected_peers: 0,
+            ever_connected: true,
+        };
+        assert_eq!(
+            signals(checks.failing(&everything)),
+            ["loop_stalled", "listeners_not_ready", "event_queue_lag", "no_connected_peers"]
+        );
+    }
+
+    #[test]
+    fn no_peers_is_fine_before_the_first_one() {
+        let fresh = Signals { connected_peers: 0, ever_connected: false, ..healthy() };
+
+        assert!(all_checks().failing(&fresh).is_empty());
+    }
+}
@@ -0,0 +1,60 @@
+use std::{collections::VecDeque, env, time::Duration};
+
+use serde::Serialize;
+
+const DEFAULT_INTERVAL_SECS: u64 = 60;
+const DEFAULT_SAMPLES: usize = 24 * 60;
+
+/// Peer population at one point in time.
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct Sample {
+    pub timestamp: i64,
+    pub total: usize,
+    pub connected: usize,
+    pub reachable: usize,
+}
+
+/// Ring buffer of peer count samples served at `/stats/history`.
+#[derive(Serialize, Debug, Clone)]
+pub struct History {
+    interval_secs: u64,
+    #[serde(skip)]
+    capacity: usize,
+    samples: VecDeque<Sample>,
+}
+
+impl History {
+    /// Uses `BOTUN_AURA_HISTORY_INTERVAL_SECS` and `BOTUN_AURA_HISTORY_SAMPLES`,
+    /// one sample a minute for a day by default.
+    pub fn from_env() -> Self {
+        let interval_secs = env::var("BOTUN_AURA_HISTORY_INTERVAL_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|&secs| secs > 0)
+            .unwrap_or(DEFAULT_INTERVAL_SECS);
+        let capacity = env::var("BOTUN_AURA_HISTORY_SAMPLES")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_SAMPLES);
+
+        History {
+            interval_secs,
+            capacity,
+            samples: VecDeque::new(),
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs)
+    }
+
+    pub fn push(&mut self, sample: Sample) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+}
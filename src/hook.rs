@@ -0,0 +1,81 @@
+use std::{env, path::PathBuf, sync::Arc, time::Duration};
+
+use tokio::{process::Command, sync::Semaphore, time::Instant};
+
+use crate::events::PeerEvent;
+
+const DEFAULT_CONCURRENCY: usize = 4;
+const DEFAULT_MIN_INTERVAL_MS: u64 = 100;
+
+/// Local program run on peer events, configured with `BOTUN_AURA_ON_PEER_EVENT`.
+///
+/// It is invoked as `<program> <event> <peer id> <namespace>`, at most
+/// `BOTUN_AURA_ON_PEER_EVENT_CONCURRENCY` at a time and no more often than
+/// every `BOTUN_AURA_ON_PEER_EVENT_INTERVAL_MS`.
+pub struct CommandHook {
+    program: PathBuf,
+    permits: Arc<Semaphore>,
+    min_interval: Duration,
+    last_run: Option<Instant>,
+}
+
+impl CommandHook {
+    pub fn from_env() -> Option<Self> {
+        let program = env::var_os("BOTUN_AURA_ON_PEER_EVENT")?;
+        let concurrency = env::var("BOTUN_AURA_ON_PEER_EVENT_CONCURRENCY")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|&n| n > 0)
+            .unwrap_or(DEFAULT_CONCURRENCY);
+        let min_interval_ms = env::var("BOTUN_AURA_ON_PEER_EVENT_INTERVAL_MS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_MIN_INTERVAL_MS);
+
+        tracing::info!("Running {:?} on peer events", program);
+
+        Some(CommandHook {
+            program: program.into(),
+            permits: Arc::new(Semaphore::new(concurrency)),
+            min_interval: Duration::from_millis(min_interval_ms),
+            last_run: None,
+        })
+    }
+
+    /// Starts the program for `event`, waiting for the throttle interval and
+    /// a free concurrency slot first. Does not wait for the program to exit.
+    pub async fn run(&mut self, event: &PeerEvent) {
+        if let Some(last_run) = self.last_run {
+            tokio::time::sleep_until(last_run + self.min_interval).await;
+        }
+        let permit = self.permits.clone().acquire_owned().await.unwrap();
+        self.last_run = Some(Instant::now());
+
+        let mut command = Command::new(&self.program);
+        command
+            .arg(event.kind())
+            .arg(event.peer().to_string())
+            .arg(event.namespace());
+
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                tracing::error!("Failed to run {:?}: {}", self.program, e);
+                return;
+            }
+        };
+
+        let program = self.program.clone();
+        let description = format!("{} {}", event.kind(), event.peer());
+        tokio::spawn(async move {
+            match child.wait().await {
+                Ok(status) if !status.success() => {
+                    tracing::warn!("{:?} for {} exited with {}", program, description, status);
+                }
+                Ok(_) => {}
+                Err(e) => tracing::error!("Failed to wait for {:?}: {}", program, e),
+            }
+            drop(permit);
+        });
+    }
+}
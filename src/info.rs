@@ -0,0 +1,306 @@
+use std::{collections::{HashMap, HashSet, VecDeque}, env};
+
+use libp2p::{core::transport::ListenerId, multiaddr::Protocol, Multiaddr, PeerId, Swarm, swarm::NetworkBehaviour};
+use serde::Serialize;
+
+const DEFAULT_CONFIRMATIONS: u32 = 3;
+const DEFAULT_EXTERNAL_ADDR_TTL_SECS: i64 = 3600;
+const MAX_CANDIDATES: usize = 64;
+const MAX_RECENT_DIALS: usize = 32;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DialRecord {
+    pub peer_id: Option<String>,
+    pub at: i64,
+}
+
+/// Transports, security protocols and behaviours this node runs, so clients
+/// can pick how to dial it.
+#[derive(Serialize, Debug, Clone)]
+pub struct Capabilities {
+    pub transports: Vec<&'static str>,
+    pub security: Vec<&'static str>,
+    pub muxers: Vec<&'static str>,
+    pub behaviours: Vec<&'static str>,
+}
+
+/// What this node knows about itself, served at `/info`.
+#[derive(Serialize, Debug, Clone)]
+pub struct NodeInfo {
+    pub peer_id: String,
+    /// `true` once every listener has reported at least one address, i.e.
+    /// `listen_addresses` is complete.
+    pub listening_ready: bool,
+    pub listen_addresses: Vec<String>,
+    pub external_addresses: Vec<String>,
+    /// `BOTUN_AURA_PUBLIC_DNSADDR` with `/p2p/<peer id>`, the address clients
+    /// should dial first.
+    pub preferred_address: Option<String>,
+    pub capabilities: Capabilities,
+    /// The last outbound dials, newest last.
+    pub recent_dials: VecDeque<DialRecord>,
+    #[serde(skip)]
+    pending_listeners: HashSet<ListenerId>,
+}
+
+impl NodeInfo {
+    pub fn new(peer_id: PeerId, capabilities: Capabilities, listeners: impl IntoIterator<Item = ListenerId>) -> Self {
+        let pending_listeners = listeners.into_iter().collect::<HashSet<_>>();
+        NodeInfo {
+            peer_id: peer_id.to_string(),
+            listening_ready: pending_listeners.is_empty(),
+            listen_addresses: vec![],
+            external_addresses: vec![],
+            preferred_address: None,
+            capabilities,
+            recent_dials: VecDeque::with_capacity(MAX_RECENT_DIALS),
+            pending_listeners,
+        }
+    }
+
+    pub fn listener_ready(&mut self, listener: ListenerId) {
+        self.pending_listeners.remove(&listener);
+        self.listening_ready = self.pending_listeners.is_empty();
+    }
+
+    pub fn record_dial(&mut self, peer_id: Option<PeerId>, at: i64) {
+        if self.recent_dials.len() == MAX_RECENT_DIALS {
+            self.recent_dials.pop_front();
+        }
+        self.recent_dials.push_back(DialRecord {
+            peer_id: peer_id.map(|p| p.to_string()),
+            at,
+        });
+    }
+
+    pub fn set_preferred_address(&mut self, address: &Multiaddr) {
+        self.preferred_address = Some(with_peer_id(address, &self.peer_id));
+    }
+
+    /// Addresses to bootstrap from, each ending in `/p2p/<peer id>`: the
+    /// preferred address first, then the other external addresses.
+    pub fn bootstrap_addresses(&self) -> Vec<String> {
+        let mut addresses = self.preferred_address.iter().cloned().collect::<Vec<_>>();
+        for address in &self.external_addresses {
+            let Ok(address) = address.parse::<Multiaddr>() else {
+                continue;
+            };
+            let address = with_peer_id(&address, &self.peer_id);
+            if !addresses.contains(&address) {
+                addresses.push(address);
+            }
+        }
+        addresses
+    }
+
+    pub fn refresh<B: NetworkBehaviour>(&mut self, swarm: &Swarm<B>) {
+        self.listen_addresses = swarm.listeners().map(|a| a.to_string()).collect();
+        self.external_addresses = swarm.external_addresses().map(|a| a.to_string()).collect();
+    }
+}
+
+fn with_peer_id(address: &Multiaddr, peer_id: &str) -> String {
+    match address.iter().last() {
+        Some(Protocol::P2p(_)) => address.to_string(),
+        _ => format!("{address}/p2p/{peer_id}"),
+    }
+}
+
+/// Reads `BOTUN_AURA_PUBLIC_DNSADDR`, the DNS name clients should dial this
+/// server by, e.g. `/dns4/rendezvous.example.com/tcp/64001`.
+pub fn public_dnsaddr_from_env(local_peer_id: &PeerId) -> Option<Multiaddr> {
+    let value = env::var("BOTUN_AURA_PUBLIC_DNSADDR").ok()?;
+    match parse_public_dnsaddr(&value, local_peer_id) {
+        Ok(address) => Some(address),
+        Err(e) => panic!("Invalid BOTUN_AURA_PUBLIC_DNSADDR '{value}': {e}"),
+    }
+}
+
+/// Accepts a multiaddr starting with a DNS name and, unless it is a
+/// `dnsaddr`, followed by a port. A trailing `/p2p/` must name this server
+/// and is dropped.
+fn parse_public_dnsaddr(value: &str, local_peer_id: &PeerId) -> Result<Multiaddr, String> {
+    let mut address = value.trim().parse::<Multiaddr>().map_err(|e| e.to_string())?;
+    if let Some(Protocol::P2p(peer)) = address.iter().last() {
+        if peer != *local_peer_id {
+            return Err(format!("ends in /p2p/{peer}, but this server is {local_peer_id}"));
+        }
+        address.pop();
+    }
+
+    let mut protocols = address.iter();
+    match protocols.next() {
+        Some(Protocol::Dnsaddr(_)) => Ok(address),
+        Some(Protocol::Dns(_) | Protocol::Dns4(_) | Protocol::Dns6(_)) => match protocols.next() {
+            Some(Protocol::Tcp(_) | Protocol::Udp(_)) => Ok(address),
+            _ => Err("expected a /tcp/ or /udp/ port after the DNS name".to_string()),
+        },
+        _ => Err("expected /dns/, /dns4/, /dns6/ or /dnsaddr/ first".to_string()),
+    }
+}
+
+/// Promotes external address candidates (as observed by remote peers through
+/// identify) to confirmed external addresses, and retires them again.
+///
+/// There is no AutoNAT here to probe candidates, so a candidate is confirmed
+/// once it has been reported `BOTUN_AURA_EXTERNAL_ADDR_CONFIRMATIONS` times.
+/// Observations of ephemeral outbound ports rarely repeat and are filtered
+/// out that way. Candidates and confirmed addresses that no peer reported
+/// within `BOTUN_AURA_EXTERNAL_ADDR_TTL_SECS` are forgotten, so a changed IP
+/// stops being advertised and stale candidates do not fill the table.
+pub struct ExternalAddrCandidates {
+    /// Unconfirmed candidates with their observation count and when they were
+    /// last reported.
+    seen: HashMap<Multiaddr, (u32, i64)>,
+    /// Confirmed addresses and when they were last reported.
+    confirmed: HashMap<Multiaddr, i64>,
+    confirmations: u32,
+    ttl_ms: i64,
+}
+
+impl ExternalAddrCandidates {
+    pub fn from_env() -> Self {
+        let confirmations = env::var("BOTUN_AURA_EXTERNAL_ADDR_CONFIRMATIONS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|&n| n > 0)
+            .unwrap_or(DEFAULT_CONFIRMATIONS);
+        let ttl_secs = env::var("BOTUN_AURA_EXTERNAL_ADDR_TTL_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|&n: &i64| n > 0)
+            .unwrap_or(DEFAULT_EXTERNAL_ADDR_TTL_SECS);
+
+        ExternalAddrCandidates::new(confirmations, ttl_secs * 1000)
+    }
+
+    fn new(confirmations: u32, ttl_ms: i64) -> Self {
+        ExternalAddrCandidates {
+            seen: HashMap::new(),
+            confirmed: HashMap::new(),
+            confirmations,
+            ttl_ms,
+        }
+    }
+
+    /// Records an observation of `address` at `now`. Returns `true` when this
+    /// observation confirms it.
+    pub fn observe(&mut self, address: &Multiaddr, now: i64) -> bool {
+        if let Some(last_seen) = self.confirmed.get_mut(address) {
+            *last_seen = now;
+            return false;
+        }
+
+        if !self.seen.contains_key(address) && self.seen.len() >= MAX_CANDIDATES {
+            // Make room by dropping the candidate reported least recently.
+            if let Some(oldest) = self.seen.iter().min_by_key(|(_, (_, last_seen))| *last_seen).map(|(a, _)| a.clone()) {
+                self.seen.remove(&oldest);
+            }
+        }
+
+        let (count, last_seen) = self.seen.entry(address.clone()).or_insert((0, now));
+        *count += 1;
+        *last_seen = now;
+        if *count >= self.confirmations {
+            self.seen.remove(address);
+            self.confirmed.insert(address.clone(), now);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Forgets everything not reported within the TTL and returns the
+    /// confirmed addresses that thereby expired, to be removed from the swarm.
+    pub fn expire(&mut self, now: i64) -> Vec<Multiaddr> {
+        let cutoff = now - self.ttl_ms;
+        self.seen.retain(|_, (_, last_seen)| *last_seen > cutoff);
+        let expired = self
+            .confirmed
+            .iter()
+            .filter(|(_, last_seen)| **last_seen <= cutoff)
+            .map(|(address, _)| address.clone())
+            .collect::<Vec<_>>();
+        for address in &expired {
+            self.confirmed.remove(address);
+        }
+        expired
+    }
+
+    /// Drops `address` after the swarm stopped advertising it some other way.
+    pub fn forget(&mut self, address: &Multiaddr) {
+        self.confirmed.remove(address);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn public_dnsaddr_is_validated() {
+        let local = PeerId::random();
+        let parse = |value: &str| parse_public_dnsaddr(value, &local).map(|a| a.to_string());
+
+        assert_eq!(parse("/dns4/rendezvous.example.com/tcp/64001"), Ok("/dns4/rendezvous.example.com/tcp/64001".into()));
+        assert_eq!(parse("/dnsaddr/rendezvous.example.com"), Ok("/dnsaddr/rendezvous.example.com".into()));
+        assert_eq!(
+            parse(&format!("/dns/rendezvous.example.com/udp/64001/quic-v1/p2p/{local}")),
+            Ok("/dns/rendezvous.example.com/udp/64001/quic-v1".into())
+        );
+
+        assert!(parse("rendezvous.example.com:64001").is_err());
+        assert!(parse("/ip4/192.0.2.1/tcp/64001").is_err());
+        assert!(parse("/dns4/rendezvous.example.com").is_err());
+        assert!(parse(&format!("/dns4/rendezvous.example.com/tcp/64001/p2p/{}", PeerId::random())).is_err());
+    }
+
+    #[test]
+    fn bootstrap_addresses_start_with_the_preferred_one() {
+        let mut info = NodeInfo::new(PeerId::random(), crate::SwarmOptions::default().capabilities(), []);
+        let dnsaddr = "/dns4/rendezvous.example.com/tcp/64001".parse::<Multiaddr>().unwrap();
+        info.external_addresses = vec!["/ip4/192.0.2.1/tcp/64001".into(), dnsaddr.to_string()];
+        info.set_preferred_address(&dnsaddr);
+
+        let peer_id = info.peer_id.clone();
+        assert_eq!(
+            info.bootstrap_addresses(),
+            [
+                format!("/dns4/rendezvous.example.com/tcp/64001/p2p/{peer_id}"),
+                format!("/ip4/192.0.2.1/tcp/64001/p2p/{peer_id}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn candidates_are_confirmed_and_expire() {
+        let mut candidates = ExternalAddrCandidates::new(2, 1_000);
+        let address = "/ip4/192.0.2.1/tcp/64001".parse::<Multiaddr>().unwrap();
+
+        assert!(!candidates.observe(&address, 0));
+        assert!(candidates.observe(&address, 100));
+        // Further observations keep a confirmed address alive.
+        assert!(!candidates.observe(&address, 900));
+        assert!(candidates.expire(1_500).is_empty());
+
+        assert_eq!(candidates.expire(1_900), vec![address.clone()]);
+        assert!(!candidates.observe(&address, 2_000));
+        assert!(candidates.observe(&address, 2_100));
+    }
+
+    #[test]
+    fn full_candidate_table_drops_the_oldest() {
+        let mut candidates = ExternalAddrCandidates::new(2, i64::MAX / 2);
+        let address = |port: usize| format!("/ip4/192.0.2.1/tcp/{port}").parse::<Multiaddr>().unwrap();
+        for port in 0..MAX_CANDIDATES {
+            candidates.observe(&address(port), port as i64);
+        }
+
+        // A new address still gets in and can be confirmed, at the cost of
+        // the least recently reported candidate.
+        assert!(!candidates.observe(&address(1000), 1000));
+        assert!(candidates.observe(&address(1000), 1001));
+        assert!(!candidates.observe(&address(0), 1002));
+        assert!(candidates.observe(&address(1), 1003));
+    }
+}
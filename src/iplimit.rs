@@ -0,0 +1,156 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::Infallible,
+    env, fmt,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use libp2p::{
+    core::{transport::PortUse, ConnectedPoint, Endpoint},
+    multiaddr::Protocol,
+    swarm::{
+        behaviour::{ConnectionClosed, ConnectionEstablished}, dummy, ConnectionDenied, ConnectionId, FromSwarm, NetworkBehaviour,
+        THandler, THandlerInEvent, THandlerOutEvent, ToSwarm,
+    },
+    Multiaddr, PeerId,
+};
+
+/// Established inbound connections per remote IP, shared with `/debug/connections-per-ip`.
+pub type IpCounts = Arc<Mutex<BTreeMap<IpAddr, usize>>>;
+
+/// An inbound connection was refused because its IP already holds `limit`
+/// connections.
+#[derive(Debug)]
+pub struct IpLimitExceeded {
+    pub ip: IpAddr,
+    pub limit: usize,
+}
+
+impl fmt::Display for IpLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} already holds the maximum of {} connections", self.ip, self.limit)
+    }
+}
+
+impl std::error::Error for IpLimitExceeded {}
+
+/// Caps inbound connections per remote IP at `BOTUN_AURA_MAX_CONN_PER_IP`.
+///
+/// `connection_limits` counts by PeerId, which costs nothing to mint, so one
+/// host can hold any number of connections by using many identities.
+pub struct Behaviour {
+    limit: Option<usize>,
+    connections: HashMap<ConnectionId, IpAddr>,
+    counts: IpCounts,
+}
+
+impl Behaviour {
+    pub fn new(limit: Option<usize>, counts: IpCounts) -> Self {
+        Behaviour {
+            limit,
+            connections: HashMap::new(),
+            counts,
+        }
+    }
+
+    fn check(&self, remote_addr: &Multiaddr) -> Result<(), ConnectionDenied> {
+        let (Some(limit), Some(ip)) = (self.limit, ip_of(remote_addr)) else {
+            return Ok(());
+        };
+        if self.counts.lock().unwrap().get(&ip).copied().unwrap_or(0) >= limit {
+            tracing::warn!("Refusing connection from {remote_addr}: {ip} already holds {limit} connections");
+            return Err(ConnectionDenied::new(IpLimitExceeded { ip, limit }));
+        }
+        Ok(())
+    }
+}
+
+/// Reads `BOTUN_AURA_MAX_CONN_PER_IP`; unset or `0` for no limit.
+pub fn limit_from_env() -> Option<usize> {
+    env::var("BOTUN_AURA_MAX_CONN_PER_IP")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .filter(|&n| n > 0)
+}
+
+fn ip_of(address: &Multiaddr) -> Option<IpAddr> {
+    match address.iter().next()? {
+        Protocol::Ip4(ip) => Some(ip.into()),
+        Protocol::Ip6(ip) => Some(ip.into()),
+        _ => None,
+    }
+}
+
+impl NetworkBehaviour for Behaviour {
+    type ConnectionHandler = dummy::ConnectionHandler;
+    type ToSwarm = Infallible;
+
+    fn handle_pending_inbound_connection(
+        &mut self,
+        _: ConnectionId,
+        _: &Multiaddr,
+        remote_addr: &Multiaddr,
+    ) -> Result<(), ConnectionDenied> {
+        self.check(remote_addr)
+    }
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        _: ConnectionId,
+        _: PeerId,
+        _: &Multiaddr,
+        remote_addr: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        // Several connections from the IP may have been pending at once.
+        self.check(remote_addr)?;
+        Ok(dummy::ConnectionHandler)
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        _: ConnectionId,
+        _: PeerId,
+        _: &Multiaddr,
+        _: Endpoint,
+        _: PortUse,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        Ok(dummy::ConnectionHandler)
+    }
+
+    fn on_swarm_event(&mut self, event: FromSwarm) {
+        match event {
+            FromSwarm::ConnectionEstablished(ConnectionEstablished {
+                connection_id,
+                endpoint: ConnectedPoint::Listener { send_back_addr, .. },
+                ..
+            }) => {
+                if let Some(ip) = ip_of(send_back_addr) {
+                    self.connections.insert(connection_id, ip);
+                    *self.counts.lock().unwrap().entry(ip).or_default() += 1;
+                }
+            }
+            FromSwarm::ConnectionClosed(ConnectionClosed { connection_id, .. }) => {
+                if let Some(ip) = self.connections.remove(&connection_id) {
+                    let mut counts = self.counts.lock().unwrap();
+                    if let Some(count) = counts.get_mut(&ip) {
+                        *count -= 1;
+                        if *count == 0 {
+                            counts.remove(&ip);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn on_connection_handler_event(&mut self, _: PeerId, _: ConnectionId, event: THandlerOutEvent<Self>) {
+        match event {}
+    }
+
+    fn poll(&mut self, _: &mut Context<'_>) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+        Poll::Pending
+    }
+}
@@ -0,0 +1,43 @@
+use std::{collections::HashMap, env};
+
+use crate::access::AccessList;
+
+/// Settings `POST /admin/reload` can change while the server runs.
+#[derive(Debug, Clone)]
+pub struct LiveConfig {
+    pub access: AccessList,
+    /// From `BOTUN_AURA_MAX_NAMESPACES_PER_PEER`. The rendezvous behaviour has
+    /// no per-peer limit of its own, so the cap applies to the registrations
+    /// this server tracks and reports.
+    pub max_namespaces_per_peer: Option<usize>,
+}
+
+impl LiveConfig {
+    pub fn from_env() -> Self {
+        Self::from_lookup(|name| env::var(name).ok()).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Reads the settings again, taking them from `.env` where it sets them
+    /// and from the process environment otherwise.
+    pub fn reload() -> Result<Self, String> {
+        // The suggested replacement loads into the process environment, which
+        // never overrides variables that are already set, so a reload would
+        // not see any change.
+        #[allow(deprecated)]
+        let file = dotenv::dotenv_iter()
+            .map_err(|e| format!("Cannot read .env: {e}"))?
+            .collect::<Result<HashMap<_, _>, _>>()
+            .map_err(|e| format!("Cannot parse .env: {e}"))?;
+
+        Self::from_lookup(|name| file.get(name).cloned().or_else(|| env::var(name).ok()))
+    }
+
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, String> {
+        Ok(LiveConfig {
+            access: AccessList::from_lookup(&lookup)?,
+            max_namespaces_per_peer: lookup("BOTUN_AURA_MAX_NAMESPACES_PER_PEER")
+                .and_then(|s| s.parse().ok())
+                .filter(|&n| n > 0),
+        })
+    }
+}
@@ -1,47 +1,600 @@
-use std::{collections::{HashMap, HashSet}, env, error::Error, sync::{Arc, Mutex}, time::Duration};
+use std::{collections::{hash_map::Entry, HashMap, HashSet}, env, error::Error, num::NonZeroU8, path::PathBuf, sync::{atomic::{AtomicI64, Ordering}, Arc, Mutex}, time::Duration};
 
-use axum::{routing::{get, post}, Json, Router};
+use base64::{engine::general_purpose::STANDARD, Engine};
 use futures::StreamExt;
 use libp2p::{
-    identify, identity::Keypair, multiaddr::Protocol, noise, ping, rendezvous, swarm::{NetworkBehaviour, SwarmEvent}, tcp, yamux, Multiaddr, PeerId, Swarm
+    core::{transport::{ListenerId, MemoryTransport, OptionalTransport}, upgrade, Transport},
+    allow_block_list::{self, AllowedPeers, BlockedPeers},
+    connection_limits::{self, ConnectionLimits},
+    identify, identity::Keypair, multiaddr::Protocol, noise, ping, rendezvous::{self, Namespace}, swarm::{behaviour::toggle::Toggle, dial_opts::{DialOpts, PeerCondition}, DialError, ListenError, NetworkBehaviour, SwarmEvent}, tcp, yamux, Multiaddr, PeerId, Swarm
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize, Serializer};
 use tokio::sync::mpsc;
-use tower_http::services::ServeDir;
 use tracing_subscriber::EnvFilter;
 
-use dotenv;
+use crate::{
+    access::{AccessChanges, AccessList}, api::{build_router, AppState, Command, DenyOutcome, ImportOutcome, ReloadOutcome}, auth::BasicAuth, connections::Connections, dial::DialPolicy, enquirers::Enquirers, events::{EventQueue, PeerEvent}, health::HealthChecks, history::{History, Sample}, info::{Capabilities, ExternalAddrCandidates, NodeInfo}, iplimit::IpCounts,
+    live::LiveConfig, metrics::Metrics, readyfile::ReadyFile, selfcheck::SelfCheck, throttle::DiscoverThrottle,
+};
 
+mod access;
+mod api;
+mod auth;
+mod connections;
+mod dial;
+mod enquirers;
+mod events;
+mod health;
+mod history;
+mod hook;
+mod info;
+mod iplimit;
+mod live;
+mod metrics;
+mod nats;
+mod negotiate;
+mod readyfile;
+mod selfcheck;
+mod snapshot;
+mod throttle;
 
 fn load_keypair_from_env() -> Keypair {
     let hex = env::var("BOTUN_AURA_RENDEZVOUS_SERVER_KEY")
         .expect("BOTUN_AURA_RENDEZVOUS_SERVER_KEY not set");
 
-    let key_bytes = hex::decode(hex)
-        .expect("Invalid hex in key");
-
-    assert_eq!(key_bytes.len(), 32, "Key must be exactly 32 bytes");
-
-    let mut key_array = [0u8; 32];
-    key_array.copy_from_slice(&key_bytes);
+    let key_array = parse_key_hex(&hex)
+        .unwrap_or_else(|e| panic!("Invalid BOTUN_AURA_RENDEZVOUS_SERVER_KEY: {e}"));
 
     Keypair::ed25519_from_bytes(key_array)
         .expect("Invalid Ed25519 key")
 }
 
+/// Decodes a 32 byte key from hex, tolerating surrounding whitespace and a
+/// `0x` prefix as left behind by copy and paste.
+fn parse_key_hex(value: &str) -> Result<[u8; 32], String> {
+    let value = value.trim();
+    let hex = value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")).unwrap_or(value);
+
+    if hex.len() != 64 {
+        return Err(format!("expected 64 hex chars, got {}", hex.len()));
+    }
+    let mut key = [0u8; 32];
+    hex::decode_to_slice(hex, &mut key).map_err(|e| format!("not valid hex: {e}"))?;
+    Ok(key)
+}
+
+/// Reads `BOTUN_AURA_SERVER_HTTP_ENDPOINT`, accepting both `host:port` and
+/// `http://host:port`.
+fn http_endpoint_from_env() -> String {
+    let value = env::var("BOTUN_AURA_SERVER_HTTP_ENDPOINT").expect("Http endpoint is not set");
+    match normalize_http_endpoint(&value) {
+        Ok(endpoint) => endpoint,
+        Err(e) => panic!("Invalid BOTUN_AURA_SERVER_HTTP_ENDPOINT '{value}': {e}"),
+    }
+}
+
+/// Turns an endpoint given with or without `http://` into `host:port`.
+fn normalize_http_endpoint(value: &str) -> Result<String, String> {
+    let value = value.trim();
+    let lower = value.to_ascii_lowercase();
+
+    if lower.starts_with("https://") {
+        return Err("the server does not terminate TLS; \
+            put a TLS reverse proxy in front of it and use a plain host:port here".to_string());
+    }
+    let host_port = match lower.strip_prefix("http://") {
+        Some(_) => &value["http://".len()..],
+        None if value.contains("://") => return Err("only http:// is supported".to_string()),
+        None => value,
+    };
+    let host_port = host_port.strip_suffix('/').unwrap_or(host_port);
+
+    if host_port.contains('/') {
+        return Err("a path is not allowed, expected host:port".to_string());
+    }
+    let Some((host, port)) = host_port.rsplit_once(':') else {
+        return Err("missing port, expected host:port".to_string());
+    };
+    if host.is_empty() {
+        return Err("missing host, expected host:port".to_string());
+    }
+    if port.parse::<u16>().is_err() {
+        return Err(format!("invalid port '{port}'"));
+    }
+
+    Ok(host_port.to_string())
+}
+
+/// Current time in milliseconds since the Unix epoch. Every timestamp the
+/// server stores or reports is taken from here, so they all share one clock;
+/// rendering them in a time zone is up to the client.
+fn now_ms() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+/// Whether the boolean option `name` is turned on, as `1` or `true`.
+fn env_flag(name: &str) -> bool {
+    env::var(name).is_ok_and(|s| s == "1" || s.eq_ignore_ascii_case("true"))
+}
+
+/// Whether `BOTUN_AURA_API_ONLY=1` turns off serving the dashboard.
+fn api_only_from_env() -> bool {
+    env_flag("BOTUN_AURA_API_ONLY")
+}
+
+/// The dashboard directories from `BOTUN_AURA_STATIC_DIRS`, first match wins,
+/// `dist` by default and none in API-only mode.
+fn static_dirs_from_env() -> Vec<PathBuf> {
+    if api_only_from_env() {
+        return vec![];
+    }
+    let Ok(value) = env::var("BOTUN_AURA_STATIC_DIRS") else {
+        return vec!["dist".into()];
+    };
+    let dirs: Vec<PathBuf> = value.split(',').map(str::trim).filter(|s| !s.is_empty()).map(PathBuf::from).collect();
+    if dirs.is_empty() {
+        panic!("Invalid BOTUN_AURA_STATIC_DIRS '{value}': no directory given");
+    }
+    for dir in dirs.iter().filter(|dir| !dir.is_dir()) {
+        tracing::warn!("Static directory {} does not exist", dir.display());
+    }
+    dirs
+}
+
 #[derive(Serialize, Debug, Clone)]
 struct AddrInfo {
     address: String,
 }
 
+/// Address counts derived from a peer's `addrinfo`, for quick triage of
+/// relay-only or IPv6-only peers.
+#[derive(Serialize, Debug, Default)]
+struct AddrSummary {
+    addr_count: usize,
+    has_ipv4: bool,
+    has_ipv6: bool,
+    has_relay: bool,
+}
+
+impl AddrSummary {
+    fn from_addrinfo(addrinfo: &[AddrInfo]) -> Self {
+        let mut summary = AddrSummary {
+            addr_count: addrinfo.len(),
+            ..Default::default()
+        };
+
+        for addr in addrinfo {
+            let Ok(ma) = addr.address.parse::<Multiaddr>() else {
+                continue;
+            };
+            for protocol in ma.iter() {
+                match protocol {
+                    Protocol::Ip4(_) | Protocol::Dns4(_) => summary.has_ipv4 = true,
+                    Protocol::Ip6(_) | Protocol::Dns6(_) => summary.has_ipv6 = true,
+                    Protocol::P2pCircuit => summary.has_relay = true,
+                    _ => {}
+                }
+            }
+        }
+
+        summary
+    }
+}
+
+/// Serializes `addrinfo` together with its [`AddrSummary`], so the counts are
+/// always computed from the addresses being sent.
+fn serialize_addrinfo<S: Serializer>(addrinfo: &[AddrInfo], serializer: S) -> Result<S::Ok, S::Error> {
+    #[derive(Serialize)]
+    struct Fields<'a> {
+        addrinfo: &'a [AddrInfo],
+        #[serde(flatten)]
+        summary: AddrSummary,
+    }
+
+    Fields { addrinfo, summary: AddrSummary::from_addrinfo(addrinfo) }.serialize(serializer)
+}
+
+const DEFAULT_MAX_CONN_PER_PEER: u32 = 2;
+
+/// How often the probe tick dials the registered peers.
+const PROBE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Reads `BOTUN_AURA_TICK_JITTER_MS`, capped at [`PROBE_INTERVAL`] so every
+/// peer is still dialed once per interval.
+fn tick_jitter_from_env() -> Duration {
+    let jitter = Duration::from_millis(
+        env::var("BOTUN_AURA_TICK_JITTER_MS").ok().and_then(|s| s.parse().ok()).unwrap_or(0),
+    );
+    if jitter > PROBE_INTERVAL {
+        tracing::warn!("BOTUN_AURA_TICK_JITTER_MS is longer than the probe interval, using {}ms", PROBE_INTERVAL.as_millis());
+        return PROBE_INTERVAL;
+    }
+    jitter
+}
+
+/// A uniformly random delay below `jitter`, zero when there is no jitter.
+fn random_delay(jitter: Duration) -> Duration {
+    if jitter.is_zero() {
+        return Duration::ZERO;
+    }
+    Duration::from_millis(rand::random_range(0..jitter.as_millis() as u64))
+}
+
+/// Reads `BOTUN_AURA_MAX_CONN_PER_PEER`, where `0` lifts the limit.
+fn max_connections_per_peer_from_env() -> Option<u32> {
+    let max = env::var("BOTUN_AURA_MAX_CONN_PER_PEER")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(DEFAULT_MAX_CONN_PER_PEER);
+    (max > 0).then_some(max)
+}
+
+/// Reads `BOTUN_AURA_PINNED_PEERS`, comma separated multiaddrs ending in
+/// `/p2p/<peer id>`. A peer may be listed with several addresses.
+fn pinned_peers_from_env() -> HashMap<PeerId, Vec<Multiaddr>> {
+    let mut pinned = HashMap::<PeerId, Vec<Multiaddr>>::new();
+    for s in env::var("BOTUN_AURA_PINNED_PEERS").unwrap_or_default().split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let address: Multiaddr = s.parse()
+            .unwrap_or_else(|e| panic!("Invalid multiaddr '{s}' in BOTUN_AURA_PINNED_PEERS: {e}"));
+        let Some(Protocol::P2p(peer)) = address.iter().last() else {
+            panic!("'{s}' in BOTUN_AURA_PINNED_PEERS does not end in /p2p/<peer id>");
+        };
+        pinned.entry(peer).or_default().push(address);
+    }
+    pinned
+}
+
+/// Parses a comma separated list of PeerIds from the environment variable `name`.
+fn peer_ids_from_env(name: &str) -> HashSet<PeerId> {
+    parse_peer_ids(name, &env::var(name).unwrap_or_default()).unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// Parses `value`, a comma separated list of PeerIds read from `name`.
+fn parse_peer_ids(name: &str, value: &str) -> Result<HashSet<PeerId>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse().map_err(|e| format!("Invalid PeerId '{s}' in {name}: {e}")))
+        .collect()
+}
+
+const RELAY_HOP_PROTOCOL: &str = "/libp2p/circuit/relay/0.2.0/hop";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum PeerRole {
+    Peer,
+    Relay,
+    Bootstrap,
+}
+
+/// The network's backbone nodes, from `BOTUN_AURA_BOOTSTRAP_PEERS`,
+/// `BOTUN_AURA_RELAY_PEERS` and `BOTUN_AURA_PINNED_PEERS`.
+#[derive(Debug, Default)]
+struct Infrastructure {
+    bootstrap: HashSet<PeerId>,
+    relays: HashSet<PeerId>,
+    pinned: HashMap<PeerId, Vec<Multiaddr>>,
+}
+
+impl Infrastructure {
+    fn from_env() -> Self {
+        Infrastructure {
+            bootstrap: peer_ids_from_env("BOTUN_AURA_BOOTSTRAP_PEERS"),
+            relays: peer_ids_from_env("BOTUN_AURA_RELAY_PEERS"),
+            pinned: pinned_peers_from_env(),
+        }
+    }
+
+    /// Entries for the pinned peers, so they are listed and probed before
+    /// they register.
+    fn pinned_stats<'a>(&'a self, now: i64, expected_protocol_version: Option<&'a str>) -> impl Iterator<Item = (PeerId, PeerStat)> + 'a {
+        self.pinned.iter().map(move |(peer, addresses)| {
+            let mut stat = PeerStat::new(*peer, now);
+            stat.check_version(expected_protocol_version);
+            stat.set_addresses(addresses.iter().map(|address| AddrInfo { address: address.to_string() }).collect());
+            stat.role = self.role(peer, false);
+            stat.pinned = true;
+            (*peer, stat)
+        })
+    }
+
+    /// Configured roles win; otherwise a peer advertising the relay hop
+    /// protocol through identify is a relay.
+    fn role(&self, peer: &PeerId, advertises_relay: bool) -> PeerRole {
+        if self.bootstrap.contains(peer) {
+            PeerRole::Bootstrap
+        } else if self.relays.contains(peer) || advertises_relay {
+            PeerRole::Relay
+        } else {
+            PeerRole::Peer
+        }
+    }
+}
+
+/// How `reachable` was last determined: an outbound connection attempt, a
+/// ping on an open connection or a registration. `none` until the peer has
+/// been probed.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum ReachableVia {
+    Dial,
+    Ping,
+    /// A registration, with `BOTUN_AURA_REGISTRATION_AS_PING` enabled.
+    Registration,
+    None,
+}
+
+/// What the server can tell about a peer's NAT, for `nat_status` in `/peers`.
+///
+/// `relay_only` when every advertised address is a relay circuit. Otherwise
+/// the outcome of the latest probe dial decides: `open` when it connected,
+/// `behind_nat` when it failed. `unknown` until the peer has been dialed;
+/// pings only show the existing connection is alive, so they leave it as is.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum NatStatus {
+    Open,
+    BehindNat,
+    RelayOnly,
+    Unknown,
+}
+
+/// A single rendezvous registration of a peer.
+#[derive(Serialize, Debug, Clone)]
+struct NamespaceInfo {
+    namespace: String,
+    ttl: u64,
+    expires_at: i64,
+}
+
 #[derive(Serialize, Debug, Clone)]
 struct PeerStat {
     peer: String,
+    #[serde(flatten, serialize_with = "serialize_addrinfo")]
     addrinfo: Vec<AddrInfo>,
+    namespaces: Vec<NamespaceInfo>,
     ping: Option<u64>,
+    /// Exponential moving average of `ping`, weighting new samples by
+    /// `BOTUN_AURA_PING_EMA_ALPHA`.
+    ping_ema: Option<f64>,
+    /// Whether the last probe of the peer succeeded; `None` until probed.
+    reachable: Option<bool>,
+    /// Which probe last set `reachable`.
+    reachable_via: ReachableVia,
+    nat_status: NatStatus,
+    /// When the peer was added to the table; kept across re-registrations.
+    first_seen: i64,
     last_seen: i64,
+    /// When the probe tick last dialed the peer; cleared once a connection is established.
+    #[serde(skip)]
+    dial_initiated_at: Option<i64>,
+    /// Versions reported by identify, `None` until the peer has identified itself.
+    agent_version: Option<String>,
+    protocol_version: Option<String>,
+    /// Whether `protocol_version` matches `BOTUN_AURA_EXPECTED_PROTOCOL_VERSION`;
+    /// always `true` when no version is expected.
+    version_ok: bool,
+    role: PeerRole,
+    /// Whether identify listed the relay hop protocol among the peer's protocols.
+    #[serde(skip)]
+    advertises_relay: bool,
+    /// Last time the peer registered, discovered or was returned by a discover.
+    #[serde(skip)]
+    last_activity: i64,
+    /// Set when the connection was closed for being idle; the probe tick
+    /// leaves the peer alone until it is active again.
+    #[serde(skip)]
+    idle_closed: bool,
+    /// Signed peer record of the latest registration as a base64 encoded
+    /// envelope, for clients that verify it themselves. Only sent when asked
+    /// for with `include_record=true`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signed_record: Option<String>,
+    /// Listed in `BOTUN_AURA_PINNED_PEERS`: kept in the table and probed
+    /// whether or not the peer is registered.
+    pinned: bool,
+}
+
+impl PeerStat {
+    fn new(peer: PeerId, now: i64) -> Self {
+        PeerStat {
+            peer: peer.to_string(),
+            addrinfo: vec![],
+            namespaces: vec![],
+            ping: None,
+            ping_ema: None,
+            reachable: None,
+            reachable_via: ReachableVia::None,
+            nat_status: NatStatus::Unknown,
+            first_seen: now,
+            last_seen: now,
+            dial_initiated_at: None,
+            agent_version: None,
+            protocol_version: None,
+            version_ok: true,
+            role: PeerRole::Peer,
+            advertises_relay: false,
+            last_activity: now,
+            idle_closed: false,
+            signed_record: None,
+            pinned: false,
+        }
+    }
+
+    fn record_ping(&mut self, rtt_ms: u64, alpha: f64) {
+        self.ping = Some(rtt_ms);
+        let rtt_ms = rtt_ms as f64;
+        self.ping_ema = Some(match self.ping_ema {
+            Some(ema) => alpha * rtt_ms + (1.0 - alpha) * ema,
+            None => rtt_ms,
+        });
+    }
+
+    /// Records a probe outcome. The latest one wins, whichever method it came from.
+    fn set_reachable(&mut self, reachable: bool, via: ReachableVia) {
+        self.reachable = Some(reachable);
+        self.reachable_via = via;
+        if via == ReachableVia::Dial && self.nat_status != NatStatus::RelayOnly {
+            self.nat_status = if reachable { NatStatus::Open } else { NatStatus::BehindNat };
+        }
+    }
+
+    fn set_addresses(&mut self, addrinfo: Vec<AddrInfo>) {
+        let relay_only = !addrinfo.is_empty()
+            && addrinfo.iter().all(|addr| addr.address.parse::<Multiaddr>().is_ok_and(|ma| ma.iter().any(|p| p == Protocol::P2pCircuit)));
+        self.addrinfo = addrinfo;
+        if relay_only {
+            self.nat_status = NatStatus::RelayOnly;
+        } else if self.nat_status == NatStatus::RelayOnly {
+            self.nat_status = NatStatus::Unknown;
+        }
+    }
+
+    fn mark_active(&mut self, now: i64) {
+        self.last_activity = now;
+        self.idle_closed = false;
+    }
+
+    /// Compares the known protocol version against
+    /// `BOTUN_AURA_EXPECTED_PROTOCOL_VERSION`. A peer that has not identified
+    /// yet only passes when no version is expected.
+    fn check_version(&mut self, expected_protocol_version: Option<&str>) {
+        self.version_ok = expected_protocol_version.is_none_or(|expected| self.protocol_version.as_deref() == Some(expected));
+    }
+
+    fn set_identify(&mut self, info: &identify::Info, expected_protocol_version: Option<&str>) {
+        self.agent_version = Some(info.agent_version.clone());
+        self.protocol_version = Some(info.protocol_version.clone());
+        self.check_version(expected_protocol_version);
+        self.advertises_relay = info.protocols.iter().any(|p| p.as_ref() == RELAY_HOP_PROTOCOL);
+    }
+
+    /// Records a (re-)registration as a sign of life. With `as_ping` it also
+    /// counts as a successful probe and lets the next probe tick dial the peer
+    /// again right away.
+    fn record_registration(&mut self, namespace: &Namespace, ttl: u64, now: i64, as_ping: bool) {
+        self.last_seen = now;
+        self.mark_active(now);
+        self.register(namespace, ttl, now);
+        if as_ping {
+            self.set_reachable(true, ReachableVia::Registration);
+            self.dial_initiated_at = None;
+        }
+    }
+
+    /// Records a registration, replacing an earlier one for the same namespace.
+    fn register(&mut self, namespace: &Namespace, ttl: u64, now: i64) {
+        let info = NamespaceInfo {
+            namespace: namespace.to_string(),
+            ttl,
+            expires_at: now + ttl as i64 * 1000,
+        };
+
+        match self.namespaces.iter_mut().find(|ns| *namespace == *ns.namespace) {
+            Some(existing) => *existing = info,
+            None => self.namespaces.push(info),
+        }
+    }
+
+    /// Drops `signed_record` unless `include` is set, to keep responses small.
+    fn with_record(mut self, include: bool) -> Self {
+        if !include {
+            self.signed_record = None;
+        }
+        self
+    }
+
+    /// When the earliest of the peer's registrations runs out.
+    fn next_expiry(&self) -> Option<i64> {
+        self.namespaces.iter().map(|ns| ns.expires_at).min()
+    }
+
+    /// Drops the registration for `namespace`. Returns `true` when the peer
+    /// has no registrations left.
+    fn unregister(&mut self, namespace: &Namespace) -> bool {
+        self.namespaces.retain(|ns| *namespace != *ns.namespace);
+        self.namespaces.is_empty()
+    }
+}
+
+/// Removes a single namespace registration, dropping the peer once it has none.
+/// Pinned peers stay listed without registrations. Returns `false` when the
+/// peer was not listed under `namespace`, e.g. because the registration was
+/// ignored or the peer evicted, so there is nothing to report.
+fn remove_registration(peers: &mut HashMap<PeerId, PeerStat>, peer: &PeerId, namespace: &Namespace) -> bool {
+    let Some(stat) = peers.get_mut(peer) else {
+        return false;
+    };
+    if !stat.namespaces.iter().any(|ns| *namespace == *ns.namespace) {
+        return false;
+    }
+    if stat.unregister(namespace) && !stat.pinned {
+        peers.remove(peer);
+    }
+    true
+}
+
+/// Ends the registrations whose TTL ran out by `now` and returns them. The
+/// rendezvous behaviour reports its own expiries, but imported registrations
+/// are unknown to it and only end here.
+fn expire_registrations(peers: &mut HashMap<PeerId, PeerStat>, now: i64) -> Vec<(PeerId, Namespace)> {
+    let expired = peers
+        .iter()
+        .flat_map(|(peer, stat)| {
+            stat.namespaces
+                .iter()
+                .filter(|ns| ns.expires_at <= now)
+                .filter_map(|ns| Namespace::new(ns.namespace.clone()).ok())
+                .map(|namespace| (*peer, namespace))
+        })
+        .collect::<Vec<_>>();
+    for (peer, namespace) in &expired {
+        remove_registration(peers, peer, namespace);
+    }
+    expired
 }
 
+/// Brings the connection level allow and block lists in line with `changes`.
+///
+/// The allow list behaviour only exists when the server started in allow-list
+/// mode, so switching the mode takes a restart to affect connections.
+fn apply_access_changes(behaviour: &mut MyBehaviour, changes: &AccessChanges) {
+    let parse = |peers: &[String]| peers.iter().filter_map(|p| p.parse::<PeerId>().ok()).collect::<Vec<_>>();
+
+    for peer in parse(&changes.denied_added) {
+        behaviour.blocked.block_peer(peer);
+    }
+    for peer in parse(&changes.denied_removed) {
+        behaviour.blocked.unblock_peer(peer);
+    }
+    if let Some(allowed) = behaviour.allowed.as_mut() {
+        for peer in parse(&changes.allowed_added) {
+            allowed.allow_peer(peer);
+        }
+        for peer in parse(&changes.allowed_removed) {
+            allowed.disallow_peer(peer);
+        }
+    }
+}
+
+/// Drops the registrations of peers `access` no longer allows, announcing
+/// each removed registration as [`PeerEvent::Evicted`].
+fn enforce_access(
+    access: &AccessList,
+    peers_set: &Mutex<HashMap<PeerId, PeerStat>>,
+    events: &EventQueue,
+) -> Vec<(PeerId, Vec<String>)> {
+    let evicted = access.evict_disallowed(&mut peers_set.lock().unwrap());
+    for (peer, namespaces) in &evicted {
+        tracing::info!("Evicted registrations of disallowed peer {peer}");
+        for namespace in namespaces {
+            events.publish(PeerEvent::Evicted { peer: *peer, namespace: namespace.clone() });
+        }
+    }
+    evicted
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
@@ -53,80 +606,397 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     let keypair = load_keypair_from_env();
 
-    let mut swarm :Swarm<MyBehaviour> = libp2p::SwarmBuilder::with_existing_identity(keypair)
-        .with_tokio()
-        .with_tcp(
-            tcp::Config::default(),
-            noise::Config::new,
-            yamux::Config::default,
-        )?
-        .with_behaviour(|key| MyBehaviour {
-            identify: identify::Behaviour::new(identify::Config::new(
-                "rendezvous-example/1.0.0".to_string(),
-                key.public(),
-            )),
-            rendezvous: rendezvous::server::Behaviour::new(rendezvous::server::Config::default()),
-            ping: ping::Behaviour::new(ping::Config::new().with_interval(Duration::from_secs(10))),
-        })?
-        .build();
+    let mut live_config = LiveConfig::from_env();
+    let swarm_options = SwarmOptions {
+        access: live_config.access.clone(),
+        max_connections_per_peer: max_connections_per_peer_from_env(),
+        max_connections_per_ip: iplimit::limit_from_env(),
+        ..Default::default()
+    };
+    let mut swarm = build_swarm(keypair, swarm_options.clone())?;
 
-    listen_on_all_interfaces(&mut swarm);
+    let listeners = listen_on_all_interfaces(&mut swarm);
 
     let peers_set = Arc::new(Mutex::new(HashMap::<PeerId, PeerStat>::new()));
-    let peers_clone = peers_set.clone();
+    let connections = Arc::new(Mutex::new(Connections::default()));
 
-    let api_listen = env::var("BOTUN_AURA_SERVER_HTTP_ENDPOINT").expect("Http endpoint is not set");
+    let metrics = Arc::new(Metrics::default());
+    let events = events::spawn(metrics.clone());
 
-    tokio::spawn(async move {
-        let app = Router::new()
-            .route("/peers", get({
-                let peers = peers_clone.clone();
-                move || {
-                    let peers = peers.lock().unwrap().clone().values().cloned().collect::<Vec<_>>();
-                    async move { Json(peers) }
-                }
-            }))
-            .fallback_service(ServeDir::new("dist"))
-        ;
+    let api_listen = http_endpoint_from_env();
+
+    let basic_auth = BasicAuth::from_env();
+
+    let history = Arc::new(Mutex::new(History::from_env()));
+
+    let enquirers = Arc::new(Mutex::new(Enquirers::from_env()));
+    let mut discover_throttle = DiscoverThrottle::from_env();
 
+    let node_info = Arc::new(Mutex::new(NodeInfo::new(*swarm.local_peer_id(), swarm_options.capabilities(), listeners)));
+    let mut external_candidates = ExternalAddrCandidates::from_env();
+    if let Some(address) = info::public_dnsaddr_from_env(swarm.local_peer_id()) {
+        tracing::info!("Advertising {address} as the preferred address");
+        swarm.add_external_address(address.clone());
+        let mut node_info = node_info.lock().unwrap();
+        node_info.set_preferred_address(&address);
+        node_info.refresh(&swarm);
+    }
+
+    // Identify runs as soon as a peer connects, usually before it registers,
+    // so keep the latest info of connected peers around until they do.
+    let mut identified = HashMap::<PeerId, identify::Info>::new();
+    let expected_protocol_version = env::var("BOTUN_AURA_EXPECTED_PROTOCOL_VERSION").ok();
+    let infrastructure = Infrastructure::from_env();
+    peers_set.lock().unwrap().extend(
+        infrastructure.pinned_stats(now_ms(), expected_protocol_version.as_deref())
+            .filter(|(peer, _)| live_config.access.is_allowed(peer)),
+    );
+
+    let (commands, mut commands_rx) = mpsc::channel::<Command>(32);
+
+    let self_check = selfcheck::enabled().then(|| Arc::new(Mutex::new(SelfCheck::default())));
+
+    let loop_heartbeat = Arc::new(AtomicI64::new(now_ms()));
+    let app = build_router(AppState {
+        peers: peers_set.clone(),
+        history: history.clone(),
+        enquirers: enquirers.clone(),
+        node_info: node_info.clone(),
+        metrics: metrics.clone(),
+        commands,
+        basic_auth,
+        self_check: self_check.clone(),
+        ip_counts: swarm_options.ip_counts.clone(),
+        connections: connections.clone(),
+        health: Arc::new(HealthChecks::from_env()),
+        loop_heartbeat: loop_heartbeat.clone(),
+        static_dirs: static_dirs_from_env(),
+    });
+
+    let api_metrics = metrics.clone();
+    let (http_bound_tx, mut http_bound) = tokio::sync::oneshot::channel();
+    tokio::spawn(async move {
         let listener = tokio::net::TcpListener::bind(api_listen).await.unwrap();
+        let _ = http_bound_tx.send(listener.local_addr().unwrap());
+
+        if let Some(self_check) = self_check {
+            selfcheck::spawn(listener.local_addr().unwrap(), self_check, api_metrics);
+        }
 
         axum::serve(listener, app)
             .await
             .unwrap();
     });
 
-    let mut ping_peers_tick = tokio::time::interval(Duration::from_secs(10));
+    let tick_jitter = tick_jitter_from_env();
+    // Start at a random point so servers started together do not probe in step.
+    let mut ping_peers_tick = tokio::time::interval_at(
+        tokio::time::Instant::now() + random_delay(tick_jitter),
+        PROBE_INTERVAL,
+    );
+    // Jittered probe dials, sent back to the loop once their delay has passed.
+    let (delayed_dials, mut delayed_dials_rx) = mpsc::unbounded_channel::<(PeerId, DialOpts)>();
+    let dial_policy = DialPolicy::from_env();
+    let dial_grace_secs: i64 = env::var("BOTUN_AURA_DIAL_GRACE_SECS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(20);
+    let ping_ema_alpha: f64 = env::var("BOTUN_AURA_PING_EMA_ALPHA")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .filter(|alpha| *alpha > 0.0 && *alpha <= 1.0)
+        .unwrap_or(0.2);
+    let max_conn_lifetime_secs: Option<i64> = env::var("BOTUN_AURA_MAX_CONN_LIFETIME_SECS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .filter(|&n| n > 0);
+    let registration_as_ping = env_flag("BOTUN_AURA_REGISTRATION_AS_PING");
+    let mut history_tick = tokio::time::interval(history.lock().unwrap().interval());
+    let mut ready_file = ReadyFile::from_env();
+    let (mut http_bound_pending, mut http_address) = (true, None);
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
 
     loop {
+        loop_heartbeat.store(now_ms(), Ordering::Relaxed);
+        if let Some(ready_file) = ready_file.as_mut()
+            && !ready_file.written()
+            && let Some(http_address) = http_address
+        {
+            let node_info = node_info.lock().unwrap();
+            if node_info.listening_ready {
+                ready_file.write(&node_info, http_address);
+            }
+        }
 
         tokio::select! {
+            _ = &mut shutdown => {
+                tracing::info!("Shutting down");
+                break;
+            }
+            address = &mut http_bound, if http_bound_pending => {
+                http_bound_pending = false;
+                http_address = address.ok();
+            }
             _ = ping_peers_tick.tick() => {
-                for (peer, stat) in peers_set.lock().unwrap().iter() {
+                let now = now_ms();
+                for address in external_candidates.expire(now) {
+                    tracing::info!("No peer reported external address {address} lately, no longer advertising it");
+                    swarm.remove_external_address(&address);
+                }
+                for (peer, namespace) in expire_registrations(&mut peers_set.lock().unwrap(), now) {
+                    tracing::info!("Peer {peer} registration expired for namespace '{namespace}'");
+                    events.publish(PeerEvent::Expired { peer, namespace: namespace.to_string() });
+                }
+                if let Some(throttle) = discover_throttle.as_mut() {
+                    for peer in throttle.release(now) {
+                        // A deny that arrived meanwhile outlasts the throttle.
+                        if !live_config.access.denied.contains(&peer) {
+                            tracing::info!("Unblocking discover-throttled enquirer {peer}");
+                            swarm.behaviour_mut().blocked.unblock_peer(peer);
+                        }
+                    }
+                }
+                for (peer, stat) in peers_set.lock().unwrap().iter_mut() {
+                    if stat.idle_closed {
+                        continue;
+                    }
+                    if let Some(max_idle) = max_conn_lifetime_secs
+                        && !stat.pinned
+                        && now - stat.last_activity > max_idle * 1000
+                        && swarm.is_connected(peer)
+                    {
+                        tracing::info!("Closing idle connection to {peer}");
+                        let _ = swarm.disconnect_peer_id(*peer);
+                        stat.idle_closed = true;
+                        continue;
+                    }
+                    if swarm.is_connected(peer) {
+                        // Ping keeps track of connected peers; another
+                        // connection would only run into the per-peer limit.
+                        continue;
+                    }
+                    if stat.dial_initiated_at.is_some_and(|at| now - at < dial_grace_secs * 1000) {
+                        tracing::debug!("Still connecting to {peer}, not dialing again");
+                        continue;
+                    }
+                    if !dial_policy.probes(stat.namespaces.iter().map(|ns| ns.namespace.as_str())) {
+                        continue;
+                    }
+                    let addresses = dial_policy.plan(
+                        stat.addrinfo.iter().filter_map(|addr| addr.address.parse::<Multiaddr>().ok()),
+                    );
+                    if addresses.is_empty() {
+                        tracing::debug!("No dialable address for {peer}");
+                        continue;
+                    }
+                    stat.dial_initiated_at = Some(now);
+
+                    // One address at a time, in order, so the first that connects ends the dial.
+                    let opts = DialOpts::peer_id(*peer)
+                        .addresses(addresses)
+                        .condition(PeerCondition::DisconnectedAndNotDialing)
+                        .override_dial_concurrency_factor(NonZeroU8::MIN)
+                        .build();
+                    if tick_jitter.is_zero() {
+                        tracing::info!("Checking peer: {peer}");
+                        probe_dial(&mut swarm, *peer, opts);
+                    } else {
+                        let (peer, delay, delayed_dials) = (*peer, random_delay(tick_jitter), delayed_dials.clone());
+                        tokio::spawn(async move {
+                            tokio::time::sleep(delay).await;
+                            let _ = delayed_dials.send((peer, opts));
+                        });
+                    }
+                }
+            }
+
+            Some((peer, opts)) = delayed_dials_rx.recv() => {
+                // The peer may have gone while the dial was waiting.
+                if peers_set.lock().unwrap().contains_key(&peer) {
                     tracing::info!("Checking peer: {peer}");
-                    for addr in stat.addrinfo.iter() {
-                        let ma: Multiaddr = addr.address.parse()?;
-                        if let Err(e) = swarm.dial(ma) {
-                            tracing::error!("Failed to dial {}: {}", addr.address, e);
+                    probe_dial(&mut swarm, peer, opts);
+                }
+            }
+
+            Some(command) = commands_rx.recv() => {
+                match command {
+                    Command::Deny { peer, reply } => {
+                        let newly_denied = live_config.access.deny(peer);
+                        swarm.behaviour_mut().blocked.block_peer(peer);
+                        tracing::info!("Denied peer {peer}");
+                        let evicted = enforce_access(&live_config.access, &peers_set, &events);
+                        let _ = reply.send(DenyOutcome {
+                            peer: peer.to_string(),
+                            newly_denied,
+                            evicted_namespaces: evicted.into_iter()
+                                .filter(|(evicted, _)| *evicted == peer)
+                                .flat_map(|(_, namespaces)| namespaces)
+                                .collect(),
+                        });
+                    }
+                    Command::Disconnect { peer, reply } => {
+                        let connected = swarm.is_connected(&peer);
+                        if connected {
+                            let _ = swarm.disconnect_peer_id(peer);
+                            tracing::info!("Disconnected peer {peer} on request");
+                            // Dial it again on the next probe tick.
+                            if let Some(stat) = peers_set.lock().unwrap().get_mut(&peer) {
+                                stat.dial_initiated_at = None;
+                                stat.idle_closed = false;
+                            }
+                        }
+                        let _ = reply.send(connected);
+                    }
+                    Command::Import { peers, reply } => {
+                        let now = now_ms();
+                        let mut outcome = ImportOutcome::default();
+                        let mut table = peers_set.lock().unwrap();
+                        for valid in peers {
+                            let peer = valid.peer;
+                            if !live_config.access.is_allowed(&peer) {
+                                outcome.denied += 1;
+                                continue;
+                            }
+                            let Entry::Vacant(slot) = table.entry(peer) else {
+                                outcome.already_present += 1;
+                                continue;
+                            };
+                            let Some(mut stat) = valid.into_stat(now, live_config.max_namespaces_per_peer) else {
+                                outcome.expired += 1;
+                                continue;
+                            };
+                            stat.check_version(expected_protocol_version.as_deref());
+                            if let Some(info) = identified.get(&peer) {
+                                stat.set_identify(info, expected_protocol_version.as_deref());
+                            }
+                            stat.role = infrastructure.role(&peer, stat.advertises_relay);
+                            slot.insert(stat);
+                            outcome.imported += 1;
+                        }
+                        drop(table);
+                        tracing::info!("Imported peer table: {outcome:?}");
+                        let _ = reply.send(outcome);
+                    }
+                    Command::Reload { reply } => {
+                        let outcome = LiveConfig::reload().map(|config| {
+                            let changes = live_config.access.changes(&config.access);
+                            apply_access_changes(swarm.behaviour_mut(), &changes);
+                            live_config = config;
+                            tracing::info!("Reloaded configuration: {changes:?}");
+                            if changes.allow_mode_changed {
+                                tracing::warn!("Allow-list mode changed; connections are only filtered accordingly after a restart");
+                            }
+
+                            let evicted = enforce_access(&live_config.access, &peers_set, &events);
+                            ReloadOutcome {
+                                restart_required: changes.allow_mode_changed,
+                                access: changes,
+                                max_namespaces_per_peer: live_config.max_namespaces_per_peer,
+                                evicted: evicted.into_iter().map(|(peer, namespaces)| (peer.to_string(), namespaces)).collect(),
+                            }
+                        });
+                        if let Err(e) = &outcome {
+                            tracing::error!("Reload failed, keeping the current configuration: {e}");
                         }
+                        let _ = reply.send(outcome);
                     }
                 }
             }
 
+            _ = history_tick.tick() => {
+                let sample = {
+                    let peers = peers_set.lock().unwrap();
+                    Sample {
+                        timestamp: now_ms(),
+                        total: peers.len(),
+                        connected: peers.keys().filter(|peer| swarm.is_connected(peer)).count(),
+                        reachable: peers.values().filter(|stat| stat.reachable == Some(true)).count(),
+                    }
+                };
+                history.lock().unwrap().push(sample);
+            }
+
             event = swarm.select_next_some() => {
                 match event {
-                    SwarmEvent::NewListenAddr { address, .. } => tracing::info!("Listening on {address:?}"),
-                    SwarmEvent::ConnectionEstablished { peer_id, .. } => {
+                    SwarmEvent::NewListenAddr { listener_id, address } => {
+                        tracing::info!("Listening on {address:?}");
+                        let mut node_info = node_info.lock().unwrap();
+                        node_info.refresh(&swarm);
+                        node_info.listener_ready(listener_id);
+                    }
+                    SwarmEvent::Dialing { peer_id, .. } => {
+                        node_info.lock().unwrap().record_dial(peer_id, now_ms());
+                    }
+                    SwarmEvent::ExpiredListenAddr { address, .. } => {
+                        tracing::info!("No longer listening on {address:?}");
+                        node_info.lock().unwrap().refresh(&swarm);
+                    }
+                    SwarmEvent::NewExternalAddrCandidate { address } => {
+                        tracing::debug!("External address candidate {address}");
+                        if external_candidates.observe(&address, now_ms()) {
+                            tracing::info!("Confirmed external address {address}");
+                            swarm.add_external_address(address);
+                            node_info.lock().unwrap().refresh(&swarm);
+                        }
+                    }
+                    SwarmEvent::ExternalAddrConfirmed { address } => {
+                        tracing::info!("External address confirmed: {address}");
+                        node_info.lock().unwrap().refresh(&swarm);
+                    }
+                    SwarmEvent::ExternalAddrExpired { address } => {
+                        tracing::info!("External address expired: {address}");
+                        external_candidates.forget(&address);
+                        node_info.lock().unwrap().refresh(&swarm);
+                    }
+                    SwarmEvent::ConnectionEstablished { peer_id, endpoint, num_established, .. } => {
                         tracing::info!("Connected to {}", peer_id);
+                        connections.lock().unwrap().established(peer_id, num_established.get(), now_ms());
+                        if let Some(peer_stats) = peers_set.lock().unwrap().get_mut(&peer_id) {
+                            peer_stats.dial_initiated_at = None;
+                            // A peer that reconnected by itself is subject to the lifetime
+                            // check and probing again.
+                            peer_stats.idle_closed = false;
+                            if endpoint.is_dialer() {
+                                peer_stats.set_reachable(true, ReachableVia::Dial);
+                            }
+                        }
                     }
-                    SwarmEvent::ConnectionClosed { peer_id, .. } => {
+                    SwarmEvent::ConnectionClosed { peer_id, num_established, .. } => {
                         tracing::info!("Disconnected from {}", peer_id);
+                        connections.lock().unwrap().closed(&peer_id, num_established);
+                        if num_established == 0 {
+                            identified.remove(&peer_id);
+                        }
+                    }
+                    SwarmEvent::Behaviour(MyBehaviourEvent::Identify(identify::Event::Received { peer_id, info, .. })) => {
+                        tracing::debug!(peer = %peer_id, "Identified as {} ({})", info.agent_version, info.protocol_version);
+                        if let Some(stat) = peers_set.lock().unwrap().get_mut(&peer_id) {
+                            stat.set_identify(&info, expected_protocol_version.as_deref());
+                            stat.role = infrastructure.role(&peer_id, stat.advertises_relay);
+                        }
+                        identified.insert(peer_id, info);
                     }
                     SwarmEvent::Behaviour(MyBehaviourEvent::Rendezvous(
                             rendezvous::server::Event::RegistrationExpired( registration ),
                     )) => {
-                        tracing::info!( "Peer {} registeration expired", registration.record.peer_id() );
-                        peers_set.lock().unwrap().remove(&registration.record.peer_id());
+                        let peer = registration.record.peer_id();
+                        tracing::info!(
+                            "Peer {} registeration expired for namespace '{}'",
+                            peer,
+                            registration.namespace
+                        );
+                        if remove_registration(&mut peers_set.lock().unwrap(), &peer, &registration.namespace) {
+                            events.publish(PeerEvent::Expired { peer, namespace: registration.namespace.to_string() });
+                        }
+                    }
+                    SwarmEvent::Behaviour(MyBehaviourEvent::Rendezvous(
+                            rendezvous::server::Event::PeerUnregistered { peer, namespace },
+                    )) => {
+                        tracing::info!("Peer {} unregistered from namespace '{}'", peer, namespace);
+                        if remove_registration(&mut peers_set.lock().unwrap(), &peer, &namespace) {
+                            events.publish(PeerEvent::Unregistered { peer, namespace: namespace.to_string() });
+                        }
                     }
                     SwarmEvent::Behaviour(MyBehaviourEvent::Rendezvous(
                             rendezvous::server::Event::PeerRegistered { peer, registration },
@@ -137,6 +1007,24 @@ async fn main() -> Result<(), Box<dyn Error>> {
                             registration.namespace
                         );
 
+                        if !live_config.access.is_allowed(&peer) {
+                            tracing::warn!("Ignoring registration of denied peer {peer}");
+                            continue;
+                        }
+
+                        if let Some(max) = live_config.max_namespaces_per_peer
+                            && let Some(stat) = peers_set.lock().unwrap().get(&peer)
+                            && stat.namespaces.len() >= max
+                            && !stat.namespaces.iter().any(|ns| registration.namespace == *ns.namespace)
+                        {
+                            tracing::warn!(
+                                "Ignoring registration of peer {} for namespace '{}': already registered under {} namespaces",
+                                peer, registration.namespace, max
+                            );
+                            metrics.count_protocol_error("rendezvous_namespace_cap");
+                            continue;
+                        }
+
                         let mut addresses = vec![];
 
                         for address in registration.record.addresses() {
@@ -155,13 +1043,23 @@ async fn main() -> Result<(), Box<dyn Error>> {
                             });
                         }
 
-                        peers_set.lock().unwrap().insert(peer,
-                            PeerStat {
-                                peer: peer.to_string(),
-                                addrinfo: addresses,
-                                last_seen: chrono::Local::now().timestamp(),
-                                ping: None,
-                            });
+                        let now = now_ms();
+                        let mut peers = peers_set.lock().unwrap();
+                        let stat = peers.entry(peer).or_insert_with(|| {
+                            let mut stat = PeerStat::new(peer, now);
+                            stat.check_version(expected_protocol_version.as_deref());
+                            if let Some(info) = identified.get(&peer) {
+                                stat.set_identify(info, expected_protocol_version.as_deref());
+                            }
+                            stat.role = infrastructure.role(&peer, stat.advertises_relay);
+                            stat
+                        });
+                        stat.set_addresses(addresses);
+                        stat.signed_record = Some(STANDARD.encode(registration.record.to_signed_envelope().into_protobuf_encoding()));
+                        stat.record_registration(&registration.namespace, registration.ttl, now, registration_as_ping);
+                        drop(peers);
+
+                        events.publish(PeerEvent::Registered { peer, namespace: registration.namespace.to_string() });
 
                     }
                     SwarmEvent::Behaviour(MyBehaviourEvent::Rendezvous(
@@ -175,6 +1073,39 @@ async fn main() -> Result<(), Box<dyn Error>> {
                             enquirer,
                             registrations.len()
                         );
+
+                        let now = now_ms();
+                        enquirers.lock().unwrap().record(enquirer, registrations.is_empty(), now);
+                        if let Some(throttle) = discover_throttle.as_mut()
+                            && throttle.record(enquirer, now)
+                        {
+                            tracing::warn!(
+                                "Blocking enquirer {enquirer}: more than {} discovers in a minute",
+                                throttle.max_per_min()
+                            );
+                            swarm.behaviour_mut().blocked.block_peer(enquirer);
+                            metrics.discovers_throttled.fetch_add(1, Ordering::Relaxed);
+                        }
+                        {
+                            let mut peers = peers_set.lock().unwrap();
+                            let served = registrations.iter().map(|r| r.record.peer_id());
+                            for peer in std::iter::once(enquirer).chain(served) {
+                                if let Some(stat) = peers.get_mut(&peer) {
+                                    stat.mark_active(now);
+                                }
+                            }
+                        }
+
+                        let mut namespaces = registrations.iter().map(|r| r.namespace.to_string()).collect::<Vec<_>>();
+                        namespaces.sort();
+                        namespaces.dedup();
+                        if namespaces.is_empty() {
+                            namespaces.push(String::new());
+                        }
+                        let mut discovers = metrics.discovers.lock().unwrap();
+                        for namespace in namespaces {
+                            *discovers.entry(namespace).or_default() += 1;
+                        }
                     }
 
                     SwarmEvent::Behaviour(MyBehaviourEvent::Ping(ping::Event {
@@ -183,9 +1114,83 @@ async fn main() -> Result<(), Box<dyn Error>> {
                         ..
                     })) => {
                         tracing::info!(%peer, "Ping is {}ms", rtt.as_millis());
+                        connections.lock().unwrap().record_ping(&peer, rtt.as_millis() as u64);
+                        if let Some(peer_stats) = peers_set.lock().unwrap().get_mut(&peer) {
+                            peer_stats.record_ping(rtt.as_millis() as u64, ping_ema_alpha);
+                            peer_stats.set_reachable(true, ReachableVia::Ping);
+                            peer_stats.last_seen = now_ms();
+                        }
+                    }
+
+                    SwarmEvent::Behaviour(MyBehaviourEvent::Ping(ping::Event {
+                        peer,
+                        result: Err(e),
+                        ..
+                    })) => {
+                        // A peer without ping is still connected, that says nothing about its reachability.
+                        let unreachable = !matches!(e, ping::Failure::Unsupported);
+                        let kind = match e {
+                            ping::Failure::Timeout => {
+                                tracing::info!(%peer, "Ping timed out");
+                                "ping_timeout"
+                            }
+                            ping::Failure::Unsupported => {
+                                tracing::debug!(%peer, "Peer does not support ping");
+                                "ping_unsupported"
+                            }
+                            ping::Failure::Other { error } => {
+                                tracing::warn!(%peer, "Ping failed: {error}");
+                                "ping_other"
+                            }
+                        };
+                        metrics.count_protocol_error(kind);
+                        if unreachable
+                            && let Some(peer_stats) = peers_set.lock().unwrap().get_mut(&peer)
+                        {
+                            peer_stats.set_reachable(false, ReachableVia::Ping);
+                        }
+                    }
+
+                    SwarmEvent::Behaviour(MyBehaviourEvent::Rendezvous(
+                            rendezvous::server::Event::DiscoverNotServed { enquirer, error },
+                    )) => {
+                        tracing::warn!("Failed to serve discover for peer {}: {:?}", enquirer, error);
+                        metrics.count_protocol_error("rendezvous_discover_not_served");
+                    }
+                    SwarmEvent::Behaviour(MyBehaviourEvent::Rendezvous(
+                            rendezvous::server::Event::PeerNotRegistered { peer, namespace, error },
+                    )) => {
+                        tracing::warn!("Declined registration of peer {} for namespace '{}': {:?}", peer, namespace, error);
+                        metrics.count_protocol_error("rendezvous_registration_declined");
+                    }
+
+                    SwarmEvent::Behaviour(MyBehaviourEvent::Identify(identify::Event::Error { peer_id, error, .. })) => {
+                        tracing::warn!(peer = %peer_id, "Identify failed: {error}");
+                        metrics.count_protocol_error("identify");
+                    }
+                    SwarmEvent::Behaviour(MyBehaviourEvent::Identify(
+                        identify::Event::Sent { peer_id, .. } | identify::Event::Pushed { peer_id, .. },
+                    )) => {
+                        tracing::trace!(peer = %peer_id, "Sent identify info");
+                    }
+
+                    SwarmEvent::IncomingConnectionError { send_back_addr, error: ListenError::Denied { cause }, .. } => {
+                        match cause.downcast_ref::<connection_limits::Exceeded>() {
+                            Some(exceeded) => tracing::warn!("Refused connection from {send_back_addr}: {exceeded}"),
+                            None => tracing::debug!("Refused connection from {send_back_addr}: {cause:?}"),
+                        }
+                    }
+                    // Denied by one of our own behaviours, e.g. a probe dial racing an inbound
+                    // connection to the per-peer limit; not a sign the peer is unreachable.
+                    SwarmEvent::OutgoingConnectionError { peer_id, error: DialError::Denied { cause }, .. } => {
+                        tracing::debug!(?peer_id, "Skipped dial: {cause:?}");
+                    }
+                    SwarmEvent::OutgoingConnectionError { peer_id: Some(peer), error, .. } => {
+                        tracing::debug!(%peer, "Outgoing connection failed: {error}");
                         if let Some(peer_stats) = peers_set.lock().unwrap().get_mut(&peer) {
-                            peer_stats.ping = Some(rtt.as_millis() as u64);
-                            peer_stats.last_seen = chrono::Local::now().timestamp();
+                            peer_stats.set_reachable(false, ReachableVia::Dial);
+                            // The dial is over, so the next tick may try again.
+                            peer_stats.dial_initiated_at = None;
                         }
                     }
 
@@ -197,17 +1202,133 @@ async fn main() -> Result<(), Box<dyn Error>> {
         }
     }
 
+    if let Some(ready_file) = &ready_file {
+        ready_file.remove();
+    }
+    Ok(())
+}
+
+/// Starts a probe dial. A peer that connected or started dialing since the
+/// dial was planned needs no probe, which is not an error.
+fn probe_dial(swarm: &mut Swarm<MyBehaviour>, peer: PeerId, opts: DialOpts) {
+    match swarm.dial(opts) {
+        Ok(()) => {}
+        Err(DialError::DialPeerConditionFalse(_)) => tracing::debug!("{peer} is already connected, not dialing"),
+        Err(e) => tracing::error!("Failed to dial {peer}: {e}"),
+    }
+}
+
+/// Resolves on Ctrl-C or, on Unix, `SIGTERM`.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Cannot listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate => {}
+    }
 }
 
 #[derive(NetworkBehaviour)]
 struct MyBehaviour {
+    limits: connection_limits::Behaviour,
+    ip_limits: iplimit::Behaviour,
+    allowed: Toggle<allow_block_list::Behaviour<AllowedPeers>>,
+    blocked: allow_block_list::Behaviour<BlockedPeers>,
     identify: identify::Behaviour,
     rendezvous: rendezvous::server::Behaviour,
     ping: ping::Behaviour,
 }
 
+#[derive(Debug, Default, Clone)]
+struct SwarmOptions {
+    /// Also accept `/memory/<port>` addresses next to TCP, so tests can run
+    /// swarms in one process without binding real sockets.
+    memory_transport: bool,
+    access: AccessList,
+    /// Established connections allowed per peer, `None` for no limit.
+    max_connections_per_peer: Option<u32>,
+    /// Inbound connections allowed per remote IP, `None` for no limit.
+    max_connections_per_ip: Option<usize>,
+    /// Where the swarm keeps its per-IP connection counts.
+    ip_counts: IpCounts,
+}
+
+impl SwarmOptions {
+    /// What [`build_swarm`] sets up with these options, as reported in `/info`.
+    fn capabilities(&self) -> Capabilities {
+        let mut transports = vec!["tcp"];
+        if self.memory_transport {
+            transports.push("memory");
+        }
+
+        Capabilities {
+            transports,
+            security: vec!["noise"],
+            muxers: vec!["yamux"],
+            behaviours: vec!["connection-limits", "ip-limits", "allow-block-list", "identify", "rendezvous-server", "ping"],
+        }
+    }
+}
+
+fn build_swarm(keypair: Keypair, options: SwarmOptions) -> Result<Swarm<MyBehaviour>, Box<dyn Error>> {
+    let swarm = libp2p::SwarmBuilder::with_existing_identity(keypair)
+        .with_tokio()
+        .with_tcp(
+            tcp::Config::default(),
+            noise::Config::new,
+            yamux::Config::default,
+        )?
+        .with_other_transport(|key| -> Result<_, Box<dyn Error + Send + Sync>> {
+            if !options.memory_transport {
+                return Ok(OptionalTransport::none());
+            }
+            let transport = MemoryTransport::default()
+                .upgrade(upgrade::Version::V1)
+                .authenticate(noise::Config::new(key)?)
+                .multiplex(yamux::Config::default());
+            Ok(OptionalTransport::some(transport))
+        })?
+        .with_behaviour(|key| MyBehaviour {
+            limits: connection_limits::Behaviour::new(
+                ConnectionLimits::default().with_max_established_per_peer(options.max_connections_per_peer),
+            ),
+            ip_limits: iplimit::Behaviour::new(options.max_connections_per_ip, options.ip_counts.clone()),
+            allowed: options.access.allowed.as_ref().map(|peers| {
+                let mut allowed = allow_block_list::Behaviour::<AllowedPeers>::default();
+                for peer in peers {
+                    allowed.allow_peer(*peer);
+                }
+                allowed
+            }).into(),
+            blocked: {
+                let mut blocked = allow_block_list::Behaviour::<BlockedPeers>::default();
+                for peer in &options.access.denied {
+                    blocked.block_peer(*peer);
+                }
+                blocked
+            },
+            identify: identify::Behaviour::new(identify::Config::new(
+                "rendezvous-example/1.0.0".to_string(),
+                key.public(),
+            )),
+            rendezvous: rendezvous::server::Behaviour::new(rendezvous::server::Config::default()),
+            ping: ping::Behaviour::new(ping::Config::new().with_interval(Duration::from_secs(10))),
+        })?
+        .build();
+
+    Ok(swarm)
+}
+
 
-fn listen_on_all_interfaces<B: NetworkBehaviour>(swarm: &mut Swarm<B>) {
+fn listen_on_all_interfaces<B: NetworkBehaviour>(swarm: &mut Swarm<B>) -> Vec<ListenerId> {
     let port: u16 = env::var("BOTUN_AURA_RENDEZVOUS_SERVER_PORT")
         .ok()
         .and_then(|s| s.parse().ok())
@@ -223,7 +1344,217 @@ fn listen_on_all_interfaces<B: NetworkBehaviour>(swarm: &mut Swarm<B>) {
         .parse()
         .expect("Invalid IPv6 multiaddr");
 
-    swarm.listen_on(addr_v4).expect("Failed to listen on IPv4");
-    swarm.listen_on(addr_v6).expect("Failed to listen on IPv6");
+    vec![
+        swarm.listen_on(addr_v4).expect("Failed to listen on IPv4"),
+        swarm.listen_on(addr_v6).expect("Failed to listen on IPv6"),
+    ]
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addresses(addresses: &[&str]) -> Vec<AddrInfo> {
+        addresses.iter().map(|address| AddrInfo { address: address.to_string() }).collect()
+    }
+
+    #[test]
+    fn nat_status_follows_probe_dials() {
+        let mut stat = PeerStat::new(PeerId::random(), 0);
+        stat.set_addresses(addresses(&["/ip4/192.0.2.1/tcp/4001"]));
+        assert_eq!(stat.nat_status, NatStatus::Unknown);
+
+        stat.set_reachable(false, ReachableVia::Dial);
+        assert_eq!(stat.nat_status, NatStatus::BehindNat);
+
+        stat.set_reachable(true, ReachableVia::Dial);
+        stat.set_reachable(false, ReachableVia::Ping);
+        assert_eq!(stat.nat_status, NatStatus::Open);
+    }
+
+    #[test]
+    fn nat_status_is_relay_only_without_direct_addresses() {
+        let circuit = "/ip4/192.0.2.1/tcp/4001/p2p/12D3KooWDpJ7As7BWAwRMfu1VU2WCqNjvq387JEYKDBj4kx6nXTN/p2p-circuit";
+        let mut stat = PeerStat::new(PeerId::random(), 0);
+
+        stat.set_addresses(addresses(&[circuit]));
+        stat.set_reachable(false, ReachableVia::Dial);
+        assert_eq!(stat.nat_status, NatStatus::RelayOnly);
+
+        stat.set_addresses(addresses(&[circuit, "/ip4/198.51.100.7/tcp/4001"]));
+        assert_eq!(stat.nat_status, NatStatus::Unknown);
+    }
+
+    #[test]
+    fn re_registering_peer_stays_listed_without_pings() {
+        let peer = PeerId::random();
+        let (chat, files) = (Namespace::from_static("chat"), Namespace::from_static("files"));
+        let mut peers = HashMap::from([(peer, PeerStat::new(peer, 0))]);
+        peers.get_mut(&peer).unwrap().record_registration(&files, 120, 0, false);
+
+        for now in (1..=5).map(|minute| minute * 60_000) {
+            let stat = peers.get_mut(&peer).unwrap();
+            stat.set_reachable(false, ReachableVia::Ping);
+            stat.record_registration(&chat, 120, now, false);
+        }
+        // The other namespace ends; the one kept alive by re-registering stays.
+        assert!(remove_registration(&mut peers, &peer, &files));
+
+        let stat = &peers[&peer];
+        assert_eq!(stat.first_seen, 0);
+        assert_eq!(stat.last_seen, 300_000);
+        assert_eq!(stat.last_activity, 300_000);
+        assert_eq!(stat.namespaces.len(), 1);
+        assert_eq!(stat.next_expiry(), Some(420_000));
+        // Without the option a registration says nothing about reachability.
+        assert_eq!(stat.reachable, Some(false));
+
+        // Only the end of its last registration removes the peer.
+        assert!(remove_registration(&mut peers, &peer, &chat));
+        assert!(!peers.contains_key(&peer));
+    }
+
+    #[test]
+    fn registration_can_count_as_ping() {
+        let mut stat = PeerStat::new(PeerId::random(), 0);
+        stat.set_reachable(false, ReachableVia::Ping);
+        stat.dial_initiated_at = Some(0);
+
+        stat.record_registration(&Namespace::from_static("chat"), 120, 1_000, true);
+
+        assert_eq!(stat.reachable, Some(true));
+        assert_eq!(stat.reachable_via, ReachableVia::Registration);
+        assert_eq!(stat.dial_initiated_at, None);
+        assert_eq!(stat.nat_status, NatStatus::Unknown);
+    }
+
+    #[test]
+    fn removing_an_unknown_registration_reports_nothing() {
+        let (peer, evicted) = (PeerId::random(), PeerId::random());
+        let mut peers = HashMap::from([(peer, PeerStat::new(peer, 0))]);
+        peers.get_mut(&peer).unwrap().register(&Namespace::from_static("chat"), 120, 0);
+
+        // Ignored for the namespace cap, or already evicted.
+        assert!(!remove_registration(&mut peers, &peer, &Namespace::from_static("files")));
+        assert!(!remove_registration(&mut peers, &evicted, &Namespace::from_static("chat")));
+
+        assert!(remove_registration(&mut peers, &peer, &Namespace::from_static("chat")));
+        assert!(peers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn swarms_connect_over_memory_transport() {
+        let options = SwarmOptions { memory_transport: true, ..SwarmOptions::default() };
+        let mut server = build_swarm(Keypair::generate_ed25519(), options.clone()).unwrap();
+        let mut client = build_swarm(Keypair::generate_ed25519(), options).unwrap();
+
+        server.listen_on("/memory/0".parse().unwrap()).unwrap();
+        let address = loop {
+            if let SwarmEvent::NewListenAddr { address, .. } = server.select_next_some().await {
+                break address;
+            }
+        };
+        assert!(matches!(address.iter().next(), Some(Protocol::Memory(port)) if port != 0));
+        client.dial(address).unwrap();
+
+        let server_id = *server.local_peer_id();
+        let connected = async {
+            loop {
+                tokio::select! {
+                    event = client.select_next_some() => {
+                        if let SwarmEvent::ConnectionEstablished { peer_id, .. } = event {
+                            break peer_id;
+                        }
+                    }
+                    _ = server.select_next_some() => {}
+                }
+            }
+        };
+        let peer = tokio::time::timeout(Duration::from_secs(10), connected).await.unwrap();
+        assert_eq!(peer, server_id);
+    }
+
+    #[test]
+    fn imported_registrations_expire() {
+        let peer = PeerId::random();
+        let import: snapshot::Import = serde_json::from_value(serde_json::json!({
+            "peers": [{
+                "peer": peer.to_string(),
+                "addrinfo": [],
+                "namespaces": [
+                    { "namespace": "chat", "ttl": 60, "expires_at": 10_000 },
+                    { "namespace": "files", "ttl": 120, "expires_at": 20_000 },
+                ],
+                "last_seen": 1_000,
+            }],
+        }))
+        .unwrap();
+        let stat = import.validate().unwrap().pop().unwrap().into_stat(5_000, None).unwrap();
+        let mut peers = HashMap::from([(peer, stat)]);
+
+        assert!(expire_registrations(&mut peers, 9_999).is_empty());
+        let expired = expire_registrations(&mut peers, 10_000);
+        assert_eq!(expired, [(peer, Namespace::from_static("chat"))]);
+        assert_eq!(peers[&peer].namespaces.len(), 1);
+
+        assert_eq!(expire_registrations(&mut peers, 20_000), [(peer, Namespace::from_static("files"))]);
+        assert!(peers.is_empty());
+    }
+
+    #[test]
+    fn pinned_peers_fail_the_version_check_until_identified() {
+        let peer = PeerId::random();
+        let infrastructure = Infrastructure {
+            bootstrap: HashSet::new(),
+            relays: HashSet::new(),
+            pinned: HashMap::from([(peer, vec![])]),
+        };
+
+        let (_, stat) = infrastructure.pinned_stats(0, Some("/botun/1.0.0")).next().unwrap();
+        assert!(!stat.version_ok);
+        let (_, stat) = infrastructure.pinned_stats(0, None).next().unwrap();
+        assert!(stat.version_ok);
+    }
+
+    #[test]
+    fn random_delay_stays_below_the_jitter() {
+        assert_eq!(random_delay(Duration::ZERO), Duration::ZERO);
+        let jitter = Duration::from_millis(50);
+        assert!((0..1000).all(|_| random_delay(jitter) < jitter));
+    }
+
+    const KEY_HEX: &str = "9f1c3b5e7a2d4f6081a3c5e7092b4d6f8a1c3e5072b4d6f8190a2c4e6f8b0d2e";
+
+    #[test]
+    fn key_hex_is_decoded() {
+        let key = parse_key_hex(KEY_HEX).unwrap();
+
+        assert_eq!(key[0], 0x9f);
+        assert_eq!(key[31], 0x2e);
+    }
+
+    #[test]
+    fn key_hex_tolerates_whitespace_and_prefix() {
+        let expected = parse_key_hex(KEY_HEX).unwrap();
+
+        for value in [
+            format!("{KEY_HEX}\n"),
+            format!("  {KEY_HEX}\t"),
+            format!("0x{KEY_HEX}"),
+            format!("0X{KEY_HEX}"),
+            format!(" 0x{}\r\n", KEY_HEX.to_uppercase()),
+        ] {
+            assert_eq!(parse_key_hex(&value), Ok(expected), "{value:?}");
+        }
+    }
+
+    #[test]
+    fn key_hex_errors_are_precise() {
+        assert_eq!(parse_key_hex(&KEY_HEX[..62]), Err("expected 64 hex chars, got 62".to_string()));
+        assert_eq!(parse_key_hex(""), Err("expected 64 hex chars, got 0".to_string()));
+        assert_eq!(parse_key_hex(&format!("0x0x{KEY_HEX}")), Err("expected 64 hex chars, got 66".to_string()));
+
+        let not_hex = format!("zz{}", &KEY_HEX[2..]);
+        assert!(parse_key_hex(&not_hex).unwrap_err().starts_with("not valid hex"));
+    }
+}
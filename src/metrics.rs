@@ -0,0 +1,116 @@
+use std::{collections::BTreeMap, fmt::Write, sync::{atomic::{AtomicU64, Ordering}, Mutex, OnceLock}};
+
+use tokio::sync::mpsc;
+
+use crate::events::PeerEvent;
+
+/// Process-wide counters and gauges, rendered in the Prometheus text format
+/// at `/metrics`.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// The peer event queue, whose depth is read off the channel; set by
+    /// `events::spawn`.
+    pub event_queue: OnceLock<mpsc::WeakSender<PeerEvent>>,
+    pub events_dropped: AtomicU64,
+    /// Peer events not published to NATS, e.g. while the broker was down.
+    pub nats_events_dropped: AtomicU64,
+    /// Peer events the hook could not keep up with.
+    pub hook_events_dropped: AtomicU64,
+    pub self_check_failures: AtomicU64,
+    /// Enquirers blocked for going over `BOTUN_AURA_MAX_DISCOVERS_PER_MIN`.
+    pub discovers_throttled: AtomicU64,
+    /// Discover requests served, by the namespace of the returned
+    /// registrations. Empty responses are counted under `""`.
+    pub discovers: Mutex<BTreeMap<String, u64>>,
+    /// Protocol level failures reported by the swarm's behaviours, by kind.
+    pub protocol_errors: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+impl Metrics {
+    /// Peer events waiting for the side-effects task.
+    pub fn event_queue_depth(&self) -> i64 {
+        self.event_queue
+            .get()
+            .and_then(mpsc::WeakSender::upgrade)
+            .map_or(0, |tx| (tx.max_capacity() - tx.capacity()) as i64)
+    }
+
+    pub fn count_protocol_error(&self, kind: &'static str) {
+        *self.protocol_errors.lock().unwrap().entry(kind).or_default() += 1;
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        gauge(&mut out, "botun_aura_event_queue_depth",
+            "Peer events waiting for the side-effects task",
+            self.event_queue_depth());
+        counter(&mut out, "botun_aura_events_dropped_total",
+            "Peer events dropped because the queue was full",
+            self.events_dropped.load(Ordering::Relaxed));
+        counter(&mut out, "botun_aura_nats_events_dropped_total",
+            "Peer events that could not be published to NATS",
+            self.nats_events_dropped.load(Ordering::Relaxed));
+        counter(&mut out, "botun_aura_hook_events_dropped_total",
+            "Peer events not passed to the hook because it was behind",
+            self.hook_events_dropped.load(Ordering::Relaxed));
+        counter(&mut out, "botun_aura_self_check_failures_total",
+            "Requests the server made to its own /health that failed or timed out",
+            self.self_check_failures.load(Ordering::Relaxed));
+        counter(&mut out, "botun_aura_discovers_throttled_total",
+            "Enquirers blocked for sending too many discovers",
+            self.discovers_throttled.load(Ordering::Relaxed));
+
+        labelled_counter(&mut out, "botun_aura_discovers_total",
+            "Discover requests served, by namespace", "namespace",
+            &self.discovers.lock().unwrap());
+        labelled_counter(&mut out, "botun_aura_protocol_errors_total",
+            "Rendezvous, identify and ping failures, by kind", "kind",
+            &self.protocol_errors.lock().unwrap());
+
+        out
+    }
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: i64) {
+    let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} gauge\n{name} {value}");
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: u64) {
+    let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} counter\n{name} {value}");
+}
+
+fn labelled_counter<K: AsRef<str>>(out: &mut String, name: &str, help: &str, label: &str, values: &BTreeMap<K, u64>) {
+    let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} counter");
+    for (value, count) in values {
+        let _ = writeln!(out, "{name}{{{label}=\"{}\"}} {count}", label_value(value.as_ref()));
+    }
+}
+
+/// Escapes a label value as required by the Prometheus text format.
+fn label_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use libp2p::PeerId;
+
+    use super::*;
+
+    #[test]
+    fn event_queue_depth_follows_the_channel() {
+        let metrics = Metrics::default();
+        assert_eq!(metrics.event_queue_depth(), 0);
+
+        let (tx, mut rx) = mpsc::channel(4);
+        metrics.event_queue.set(tx.downgrade()).unwrap();
+        let event = || PeerEvent::Registered { peer: PeerId::random(), namespace: "chat".into() };
+        tx.try_send(event()).unwrap();
+        tx.try_send(event()).unwrap();
+        assert_eq!(metrics.event_queue_depth(), 2);
+
+        rx.try_recv().unwrap();
+        assert_eq!(metrics.event_queue_depth(), 1);
+    }
+}
@@ -0,0 +1,236 @@
+use std::{
+    env,
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpStream,
+    },
+    sync::mpsc::{self, error::TrySendError},
+};
+
+use crate::{events::PeerEvent, metrics::Metrics, now_ms};
+
+const DEFAULT_SUBJECT: &str = "botun-aura.peer-events";
+const DEFAULT_PORT: u16 = 4222;
+/// Events kept while the broker is unreachable; later ones are dropped.
+const QUEUE_DEPTH: usize = 1024;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Where to publish, parsed from `nats://[user:pass@]host[:port]`.
+#[derive(Debug, PartialEq, Eq)]
+struct Target {
+    address: String,
+    credentials: Option<(String, String)>,
+}
+
+impl Target {
+    fn parse(url: &str) -> Result<Self, String> {
+        let rest = url.trim().strip_prefix("nats://").ok_or("expected nats://host:port")?;
+        let (credentials, host_port) = match rest.rsplit_once('@') {
+            Some((credentials, host_port)) => {
+                let (user, password) = credentials.split_once(':').ok_or("credentials must be user:pass")?;
+                (Some((user.to_string(), password.to_string())), host_port)
+            }
+            None => (None, rest),
+        };
+        let host_port = host_port.strip_suffix('/').unwrap_or(host_port);
+        if host_port.is_empty() || host_port.contains('/') {
+            return Err("expected nats://host:port".to_string());
+        }
+
+        // A colon after a closing bracket, or the only colon, starts the port.
+        let has_port = match host_port.rfind(']') {
+            Some(bracket) => host_port[bracket..].contains(':'),
+            None => host_port.matches(':').count() == 1,
+        };
+        let address = if has_port {
+            let (_, port) = host_port.rsplit_once(':').unwrap();
+            port.parse::<u16>().map_err(|_| format!("invalid port '{port}'"))?;
+            host_port.to_string()
+        } else {
+            format!("{host_port}:{DEFAULT_PORT}")
+        };
+
+        Ok(Target { address, credentials })
+    }
+}
+
+/// Forwards peer events to a NATS subject, configured with
+/// `BOTUN_AURA_NATS_URL` and `BOTUN_AURA_NATS_SUBJECT`.
+///
+/// Publishing happens on its own task that reconnects with backoff, so a
+/// broker outage never holds up the event queue. Events that arrive while
+/// the publisher's own queue is full are dropped and counted in
+/// `botun_aura_nats_events_dropped_total`.
+pub struct NatsPublisher {
+    tx: mpsc::Sender<Vec<u8>>,
+    metrics: Arc<Metrics>,
+}
+
+impl NatsPublisher {
+    pub fn from_env(metrics: Arc<Metrics>) -> Option<Self> {
+        let url = env::var("BOTUN_AURA_NATS_URL").ok()?;
+        let target = Target::parse(&url).unwrap_or_else(|e| panic!("Invalid BOTUN_AURA_NATS_URL: {e}"));
+        let subject = env::var("BOTUN_AURA_NATS_SUBJECT").unwrap_or_else(|_| DEFAULT_SUBJECT.to_string());
+        assert!(
+            !subject.is_empty() && !subject.contains(char::is_whitespace),
+            "BOTUN_AURA_NATS_SUBJECT must be a single token without whitespace"
+        );
+
+        tracing::info!("Publishing peer events to {subject} on {}", target.address);
+
+        let (tx, rx) = mpsc::channel(QUEUE_DEPTH);
+        tokio::spawn(run(target, subject, rx, metrics.clone()));
+
+        Some(NatsPublisher { tx, metrics })
+    }
+
+    /// Queues `event` for publishing as JSON. Never waits.
+    pub fn publish(&self, event: &PeerEvent) {
+        let payload = serde_json::json!({
+            "event": event.kind(),
+            "peer": event.peer().to_string(),
+            "namespace": event.namespace(),
+            "at": now_ms(),
+        });
+        match self.tx.try_send(payload.to_string().into_bytes()) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) | Err(TrySendError::Closed(_)) => {
+                tracing::debug!("NATS publisher is behind, dropping {:?}", event);
+                self.metrics.nats_events_dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+async fn run(target: Target, subject: String, mut rx: mpsc::Receiver<Vec<u8>>, metrics: Arc<Metrics>) {
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        match tokio::time::timeout(CONNECT_TIMEOUT, connect(&target)).await {
+            Ok(Ok((lines, writer))) => {
+                tracing::info!("Connected to NATS at {}", target.address);
+                backoff = MIN_BACKOFF;
+                match forward(lines, writer, &subject, &mut rx, &metrics).await {
+                    Ok(()) => return,
+                    Err(e) => tracing::warn!("Lost NATS connection to {}: {e}", target.address),
+                }
+            }
+            Ok(Err(e)) => tracing::warn!("Cannot connect to NATS at {}: {e}", target.address),
+            Err(_) => tracing::warn!("Cannot connect to NATS at {}: timed out", target.address),
+        }
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+/// Opens a connection and completes the handshake, using a `PING` to learn
+/// whether the server accepted the `CONNECT`.
+async fn connect(target: &Target) -> Result<(Lines<BufReader<OwnedReadHalf>>, OwnedWriteHalf), String> {
+    let stream = TcpStream::connect(&target.address).await.map_err(|e| e.to_string())?;
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    let info = next_line(&mut lines).await?;
+    let info = info.strip_prefix("INFO ").ok_or_else(|| format!("unexpected greeting '{info}'"))?;
+    let info: serde_json::Value = serde_json::from_str(info).map_err(|e| format!("malformed INFO: {e}"))?;
+    if info["tls_required"] == true {
+        return Err("the server requires TLS, which is not supported".to_string());
+    }
+
+    let mut options = serde_json::json!({
+        "verbose": false,
+        "pedantic": false,
+        "name": env!("CARGO_PKG_NAME"),
+        "lang": "rust",
+        "version": env!("CARGO_PKG_VERSION"),
+    });
+    if let Some((user, password)) = &target.credentials {
+        options["user"] = user.as_str().into();
+        options["pass"] = password.as_str().into();
+    }
+    writer.write_all(format!("CONNECT {options}\r\nPING\r\n").as_bytes()).await.map_err(|e| e.to_string())?;
+
+    match next_line(&mut lines).await?.as_str() {
+        "PONG" => Ok((lines, writer)),
+        other => Err(other.to_string()),
+    }
+}
+
+/// Publishes queued events until the connection fails, answering the
+/// server's keep-alive `PING`s meanwhile. Returns `Ok` once the queue closes.
+async fn forward(
+    mut lines: Lines<BufReader<OwnedReadHalf>>,
+    mut writer: OwnedWriteHalf,
+    subject: &str,
+    rx: &mut mpsc::Receiver<Vec<u8>>,
+    metrics: &Metrics,
+) -> Result<(), String> {
+    loop {
+        tokio::select! {
+            payload = rx.recv() => {
+                let Some(payload) = payload else {
+                    return Ok(());
+                };
+                let mut message = format!("PUB {subject} {}\r\n", payload.len()).into_bytes();
+                message.extend_from_slice(&payload);
+                message.extend_from_slice(b"\r\n");
+                if let Err(e) = writer.write_all(&message).await {
+                    metrics.nats_events_dropped.fetch_add(1, Ordering::Relaxed);
+                    return Err(e.to_string());
+                }
+            }
+            line = next_line(&mut lines) => match line?.as_str() {
+                "PING" => writer.write_all(b"PONG\r\n").await.map_err(|e| e.to_string())?,
+                error if error.starts_with("-ERR") => return Err(error.to_string()),
+                _ => {}
+            },
+        }
+    }
+}
+
+async fn next_line(lines: &mut Lines<BufReader<OwnedReadHalf>>) -> Result<String, String> {
+    match lines.next_line().await {
+        Ok(Some(line)) => Ok(line),
+        Ok(None) => Err("connection closed".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_urls() {
+        let target = |address: &str| Target { address: address.to_string(), credentials: None };
+
+        assert_eq!(Target::parse("nats://127.0.0.1:4223"), Ok(target("127.0.0.1:4223")));
+        assert_eq!(Target::parse("nats://broker.example"), Ok(target("broker.example:4222")));
+        assert_eq!(Target::parse(" nats://broker.example/ "), Ok(target("broker.example:4222")));
+        assert_eq!(Target::parse("nats://[::1]"), Ok(target("[::1]:4222")));
+        assert_eq!(Target::parse("nats://[::1]:4223"), Ok(target("[::1]:4223")));
+        assert_eq!(
+            Target::parse("nats://aura:s3cr@t@broker.example"),
+            Ok(Target {
+                address: "broker.example:4222".to_string(),
+                credentials: Some(("aura".to_string(), "s3cr@t".to_string())),
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_urls() {
+        assert!(Target::parse("broker.example:4222").is_err());
+        assert!(Target::parse("nats://").is_err());
+        assert!(Target::parse("nats://broker.example:port").is_err());
+        assert!(Target::parse("nats://broker.example/subject").is_err());
+        assert!(Target::parse("nats://token@broker.example").is_err());
+    }
+}
@@ -0,0 +1,84 @@
+use axum::{
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+
+const MSGPACK: &str = "application/msgpack";
+
+/// Serializes `value` as MessagePack when the request's `Accept` header asks
+/// for it, and as JSON otherwise.
+///
+/// The MessagePack encoding keeps field names, so it decodes to the same
+/// structure as the JSON one.
+pub fn negotiated<T: Serialize>(headers: &HeaderMap, value: &T) -> Response {
+    if !accepts_msgpack(headers) {
+        return Json(value).into_response();
+    }
+
+    match rmp_serde::to_vec_named(value) {
+        Ok(body) => ([(header::CONTENT_TYPE, HeaderValue::from_static(MSGPACK))], body).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to encode MessagePack response: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+fn accepts_msgpack(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|media| media.split(';').next().unwrap_or("").trim())
+        .any(|media| media.eq_ignore_ascii_case(MSGPACK) || media.eq_ignore_ascii_case("application/x-msgpack"))
+}
+
+#[cfg(test)]
+mod tests {
+    use libp2p::{rendezvous::Namespace, PeerId};
+
+    use super::*;
+    use crate::PeerStat;
+
+    async fn body(response: Response) -> Vec<u8> {
+        axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap().to_vec()
+    }
+
+    fn peers() -> Vec<PeerStat> {
+        (0..3)
+            .map(|_| {
+                let mut stat = PeerStat::new(PeerId::random(), 1_700_000_000);
+                stat.register(&Namespace::from_static("test"), 7200, 1_700_000_000);
+                stat.record_ping(42, 0.2);
+                stat
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn json_is_the_default() {
+        let response = negotiated(&HeaderMap::new(), &peers());
+
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+    }
+
+    #[tokio::test]
+    async fn msgpack_round_trips_to_the_json_structure() {
+        let peers = peers();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_static("application/json;q=0.5, application/msgpack"));
+
+        let response = negotiated(&headers, &peers);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], MSGPACK);
+        let packed = body(response).await;
+
+        let decoded: serde_json::Value = rmp_serde::from_slice(&packed).unwrap();
+        assert_eq!(decoded, serde_json::to_value(&peers).unwrap());
+
+        let json = body(negotiated(&HeaderMap::new(), &peers)).await;
+        assert!(packed.len() < json.len());
+    }
+}
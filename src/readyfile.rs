@@ -0,0 +1,118 @@
+use std::{
+    env, fs, io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::info::NodeInfo;
+
+/// What the ready file holds.
+#[derive(Serialize, Debug)]
+struct Contents<'a> {
+    peer_id: &'a str,
+    listen_addresses: &'a [String],
+    http_address: SocketAddr,
+    pid: u32,
+}
+
+/// `BOTUN_AURA_READY_FILE`: a JSON file written once the server is ready and
+/// removed again on shutdown, for supervisors that watch the filesystem
+/// rather than `/ready`. Failing to write or remove it is logged, never fatal.
+#[derive(Debug)]
+pub struct ReadyFile {
+    path: PathBuf,
+    written: bool,
+}
+
+impl ReadyFile {
+    pub fn from_env() -> Option<Self> {
+        let path = PathBuf::from(env::var("BOTUN_AURA_READY_FILE").ok().filter(|s| !s.is_empty())?);
+        // A file left behind by a crash would claim readiness too early.
+        match fs::remove_file(&path) {
+            Ok(()) => tracing::info!("Removed stale ready file {}", path.display()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => tracing::warn!("Cannot remove stale ready file {}: {e}", path.display()),
+        }
+        Some(ReadyFile { path, written: false })
+    }
+
+    pub fn written(&self) -> bool {
+        self.written
+    }
+
+    /// Writes the file, once. Only call this when every listener has
+    /// reported an address and the HTTP API is bound to `http_address`.
+    pub fn write(&mut self, node_info: &NodeInfo, http_address: SocketAddr) {
+        if self.written {
+            return;
+        }
+        // Marked written even on failure, so the error is logged only once.
+        self.written = true;
+
+        let contents = Contents {
+            peer_id: &node_info.peer_id,
+            listen_addresses: &node_info.listen_addresses,
+            http_address,
+            pid: std::process::id(),
+        };
+        match write_atomically(&self.path, &serde_json::to_vec_pretty(&contents).unwrap()) {
+            Ok(()) => tracing::info!("Wrote ready file {}", self.path.display()),
+            Err(e) => tracing::error!("Cannot write ready file {}: {e}", self.path.display()),
+        }
+    }
+
+    pub fn remove(&self) {
+        if !self.written {
+            return;
+        }
+        match fs::remove_file(&self.path) {
+            Ok(()) => tracing::info!("Removed ready file {}", self.path.display()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => tracing::error!("Cannot remove ready file {}: {e}", self.path.display()),
+        }
+    }
+}
+
+/// Writes a temporary file next to `path` and renames it into place, so a
+/// reader never sees a partly written file.
+fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(format!(".{}.tmp", std::process::id()));
+    let temporary = PathBuf::from(temporary);
+
+    fs::write(&temporary, contents)
+        .and_then(|()| fs::rename(&temporary, path))
+        .inspect_err(|_| {
+            let _ = fs::remove_file(&temporary);
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use libp2p::PeerId;
+
+    use super::*;
+
+    #[test]
+    fn writes_once_and_removes() {
+        let path = env::temp_dir().join(format!("botun-aura-ready-{}.json", PeerId::random()));
+        let mut info = NodeInfo::new(PeerId::random(), crate::SwarmOptions::default().capabilities(), []);
+        info.listen_addresses = vec!["/ip4/127.0.0.1/tcp/64001".into()];
+        let mut ready_file = ReadyFile { path: path.clone(), written: false };
+
+        ready_file.write(&info, "127.0.0.1:8080".parse().unwrap());
+        info.listen_addresses.clear();
+        ready_file.write(&info, "127.0.0.1:8080".parse().unwrap());
+
+        let written: serde_json::Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(written["peer_id"], info.peer_id);
+        assert_eq!(written["listen_addresses"], serde_json::json!(["/ip4/127.0.0.1/tcp/64001"]));
+        assert_eq!(written["http_address"], "127.0.0.1:8080");
+        assert_eq!(written["pid"], std::process::id());
+
+        ready_file.remove();
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,93 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::{atomic::Ordering, Arc, Mutex},
+    time::Duration,
+};
+
+use serde::Serialize;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    time::Instant,
+};
+
+use crate::{env_flag, metrics::Metrics, now_ms};
+
+const INTERVAL: Duration = Duration::from_secs(30);
+const TIMEOUT: Duration = Duration::from_secs(5);
+const SLOW: Duration = Duration::from_secs(1);
+
+/// Outcome of the latest request the server made to its own `/health`.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct SelfCheck {
+    pub ok: Option<bool>,
+    pub latency_ms: Option<u64>,
+    pub checked_at: Option<i64>,
+    pub error: Option<String>,
+}
+
+/// Whether `BOTUN_AURA_SELF_CHECK=1` asks for the self-check.
+pub fn enabled() -> bool {
+    env_flag("BOTUN_AURA_SELF_CHECK")
+}
+
+/// Requests `/health` from the API listening on `local_addr` every 30
+/// seconds, so a process that is up but no longer answering HTTP shows up in
+/// the logs and in `botun_aura_self_check_failures_total`.
+pub fn spawn(local_addr: SocketAddr, status: Arc<Mutex<SelfCheck>>, metrics: Arc<Metrics>) {
+    let addr = SocketAddr::new(loopback_for(local_addr.ip()), local_addr.port());
+
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(INTERVAL);
+        loop {
+            tick.tick().await;
+
+            let started = Instant::now();
+            let result = tokio::time::timeout(TIMEOUT, request_health(addr))
+                .await
+                .unwrap_or_else(|_| Err(format!("no response within {}s", TIMEOUT.as_secs())));
+            let elapsed = started.elapsed();
+
+            match &result {
+                Ok(()) if elapsed > SLOW => tracing::warn!("Self-check of {addr} took {}ms", elapsed.as_millis()),
+                Ok(()) => tracing::debug!("Self-check of {addr} took {}ms", elapsed.as_millis()),
+                Err(e) => {
+                    tracing::error!("Self-check of {addr} failed: {e}");
+                    metrics.self_check_failures.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+
+            *status.lock().unwrap() = SelfCheck {
+                ok: Some(result.is_ok()),
+                latency_ms: Some(elapsed.as_millis() as u64),
+                checked_at: Some(now_ms()),
+                error: result.err(),
+            };
+        }
+    });
+}
+
+/// The API may listen on a wildcard address, which cannot be dialed.
+fn loopback_for(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(ip) if ip.is_unspecified() => Ipv4Addr::LOCALHOST.into(),
+        IpAddr::V6(ip) if ip.is_unspecified() => Ipv6Addr::LOCALHOST.into(),
+        ip => ip,
+    }
+}
+
+async fn request_health(addr: SocketAddr) -> Result<(), String> {
+    let mut stream = TcpStream::connect(addr).await.map_err(|e| e.to_string())?;
+
+    let request = format!("GET /health HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\r\n");
+    stream.write_all(request.as_bytes()).await.map_err(|e| e.to_string())?;
+
+    let mut status_line = String::new();
+    BufReader::new(stream).read_line(&mut status_line).await.map_err(|e| e.to_string())?;
+    match status_line.split_whitespace().nth(1) {
+        // A degraded composite health check still means HTTP is answering.
+        Some("200") | Some("503") => Ok(()),
+        Some(status) => Err(format!("/health answered {status}")),
+        None => Err("malformed response".to_string()),
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::HashSet;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use libp2p::{
+    core::{PeerRecord, SignedEnvelope},
+    multiaddr::Protocol,
+    rendezvous::Namespace,
+    Multiaddr, PeerId,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{AddrInfo, NamespaceInfo, PeerStat};
+
+/// Largest body `POST /admin/import` accepts.
+pub const IMPORT_LIMIT_BYTES: usize = 16 * 1024 * 1024;
+
+/// The peer table as served by `GET /admin/export`.
+#[derive(Serialize, Debug)]
+pub struct Export {
+    pub exported_at: i64,
+    pub peers: Vec<PeerStat>,
+}
+
+/// The part of an export `POST /admin/import` reads. Other fields, such as
+/// the address summary or probe results, are ignored.
+#[derive(Deserialize, Debug)]
+pub struct Import {
+    pub peers: Vec<ImportedPeer>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ImportedPeer {
+    peer: String,
+    addrinfo: Vec<ImportedAddr>,
+    namespaces: Vec<ImportedNamespace>,
+    /// Missing from exports made before `first_seen` existed.
+    first_seen: Option<i64>,
+    last_seen: i64,
+    agent_version: Option<String>,
+    protocol_version: Option<String>,
+    signed_record: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ImportedAddr {
+    address: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct ImportedNamespace {
+    namespace: String,
+    ttl: u64,
+    expires_at: i64,
+}
+
+/// A validated entry, ready to be merged into the peer table.
+#[derive(Debug)]
+pub struct ValidPeer {
+    pub peer: PeerId,
+    stat: PeerStat,
+}
+
+impl Import {
+    /// Checks every entry, rejecting the whole import on the first problem.
+    pub fn validate(self) -> Result<Vec<ValidPeer>, String> {
+        let mut seen = HashSet::new();
+        self.peers
+            .into_iter()
+            .enumerate()
+            .map(|(i, imported)| {
+                let valid = imported.validate().map_err(|e| format!("peers[{i}]: {e}"))?;
+                if !seen.insert(valid.peer) {
+                    return Err(format!("peers[{i}]: {} is listed twice", valid.peer));
+                }
+                Ok(valid)
+            })
+            .collect()
+    }
+}
+
+impl ImportedPeer {
+    fn validate(self) -> Result<ValidPeer, String> {
+        let peer = self.peer.parse::<PeerId>().map_err(|e| format!("invalid peer id '{}': {e}", self.peer))?;
+
+        let mut addrinfo = vec![];
+        for ImportedAddr { address } in self.addrinfo {
+            let multiaddr = address.parse::<Multiaddr>().map_err(|e| format!("invalid address '{address}': {e}"))?;
+            if multiaddr.iter().last() != Some(Protocol::P2p(peer)) {
+                return Err(format!("address '{address}' does not end in /p2p/{peer}"));
+            }
+            addrinfo.push(AddrInfo { address });
+        }
+
+        if let Some(record) = &self.signed_record {
+            let envelope = STANDARD
+                .decode(record)
+                .map_err(|e| e.to_string())
+                .and_then(|bytes| SignedEnvelope::from_protobuf_encoding(&bytes).map_err(|e| e.to_string()))
+                .and_then(|envelope| PeerRecord::from_signed_envelope(envelope).map_err(|e| e.to_string()))
+                .map_err(|e| format!("invalid signed_record: {e}"))?;
+            if envelope.peer_id() != peer {
+                return Err(format!("signed_record was signed by {}", envelope.peer_id()));
+            }
+        }
+
+        let mut stat = PeerStat::new(peer, self.first_seen.unwrap_or(self.last_seen));
+        stat.last_seen = self.last_seen;
+        stat.set_addresses(addrinfo);
+        stat.agent_version = self.agent_version;
+        stat.protocol_version = self.protocol_version;
+        stat.signed_record = self.signed_record;
+        for ImportedNamespace { namespace, ttl, expires_at } in self.namespaces {
+            Namespace::new(namespace.clone()).map_err(|e| format!("invalid namespace '{namespace}': {e}"))?;
+            if ttl == 0 {
+                return Err(format!("namespace '{namespace}' has a ttl of 0"));
+            }
+            if stat.namespaces.iter().any(|ns| ns.namespace == namespace) {
+                return Err(format!("namespace '{namespace}' is listed twice"));
+            }
+            stat.namespaces.push(NamespaceInfo { namespace, ttl, expires_at });
+        }
+
+        Ok(ValidPeer { peer, stat })
+    }
+}
+
+impl ValidPeer {
+    /// The entry as it goes into the table: registrations that ran out by
+    /// `now` are dropped, as are those beyond `max_namespaces`, and the peer
+    /// is unprobed until the next tick. `None` when no registration is left.
+    pub fn into_stat(mut self, now: i64, max_namespaces: Option<usize>) -> Option<PeerStat> {
+        self.stat.namespaces.retain(|ns| ns.expires_at > now);
+        if let Some(max) = max_namespaces {
+            self.stat.namespaces.truncate(max);
+        }
+        self.stat.mark_active(now);
+        (!self.stat.namespaces.is_empty()).then_some(self.stat)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn import(peers: serde_json::Value) -> Result<Vec<ValidPeer>, String> {
+        serde_json::from_value::<Import>(serde_json::json!({ "peers": peers }))
+            .map_err(|e| e.to_string())?
+            .validate()
+    }
+
+    fn entry(peer: PeerId) -> serde_json::Value {
+        serde_json::json!({
+            "peer": peer.to_string(),
+            "addrinfo": [{ "address": format!("/ip4/192.0.2.1/tcp/4001/p2p/{peer}") }],
+            "addr_count": 1,
+            "namespaces": [{ "namespace": "chat", "ttl": 7200, "expires_at": 10_000 }],
+            "reachable": true,
+            "nat_status": "open",
+            "last_seen": 2_000,
+            "agent_version": null,
+            "protocol_version": null,
+        })
+    }
+
+    #[test]
+    fn imports_an_export_as_unprobed() {
+        let peer = PeerId::random();
+
+        let stat = import(serde_json::json!([entry(peer)])).unwrap().pop().unwrap().into_stat(5_000, None).unwrap();
+
+        assert_eq!(stat.peer, peer.to_string());
+        assert_eq!((stat.first_seen, stat.last_seen), (2_000, 2_000));
+        assert_eq!(stat.next_expiry(), Some(10_000));
+        assert_eq!(stat.reachable, None);
+        assert_eq!(stat.dial_initiated_at, None);
+    }
+
+    #[test]
+    fn expired_registrations_are_dropped() {
+        let valid = import(serde_json::json!([entry(PeerId::random())])).unwrap().pop().unwrap();
+
+        assert!(valid.into_stat(10_000, None).is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_entries() {
+        let peer = PeerId::random();
+        let with = |field: &str, value: serde_json::Value| {
+            let mut entry = entry(peer);
+            entry[field] = value;
+            import(serde_json::json!([entry]))
+        };
+
+        assert!(with("peer", "not a peer id".into()).unwrap_err().contains("invalid peer id"));
+        assert!(with("last_seen", "yesterday".into()).is_err());
+        assert!(with("addrinfo", serde_json::json!([{ "address": "/ip4/192.0.2.1/tcp/4001" }])).unwrap_err().contains("does not end in"));
+        assert!(with("namespaces", serde_json::json!([{ "namespace": "chat", "ttl": 0, "expires_at": 1 }])).unwrap_err().contains("ttl"));
+        assert!(with("signed_record", "bm90IGFuIGVudmVsb3Bl".into()).unwrap_err().contains("signed_record"));
+        assert!(import(serde_json::json!([entry(peer), entry(peer)])).unwrap_err().contains("listed twice"));
+    }
+}
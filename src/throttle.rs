@@ -0,0 +1,133 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    env,
+};
+
+use libp2p::PeerId;
+
+const WINDOW_MS: i64 = 60_000;
+const DEFAULT_BLOCK_SECS: i64 = 300;
+
+/// Blocks enquirers that send more than `BOTUN_AURA_MAX_DISCOVERS_PER_MIN`
+/// discovers in any 60 second window, for `BOTUN_AURA_DISCOVER_BLOCK_SECS`.
+///
+/// The rendezvous server answers every discover before telling us about it,
+/// so the limit cannot refuse a single request; it stops the enquirer from
+/// sending more by putting it on the block list for a while.
+#[derive(Debug)]
+pub struct DiscoverThrottle {
+    max_per_min: usize,
+    block_ms: i64,
+    served: HashMap<PeerId, VecDeque<i64>>,
+    blocked_until: HashMap<PeerId, i64>,
+}
+
+impl DiscoverThrottle {
+    /// `None` when `BOTUN_AURA_MAX_DISCOVERS_PER_MIN` is unset or `0`.
+    pub fn from_env() -> Option<Self> {
+        let max_per_min = env::var("BOTUN_AURA_MAX_DISCOVERS_PER_MIN")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|&n| n > 0)?;
+        let block_secs = env::var("BOTUN_AURA_DISCOVER_BLOCK_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|&n| n > 0)
+            .unwrap_or(DEFAULT_BLOCK_SECS);
+
+        Some(Self::new(max_per_min, block_secs * 1000))
+    }
+
+    fn new(max_per_min: usize, block_ms: i64) -> Self {
+        DiscoverThrottle {
+            max_per_min,
+            block_ms,
+            served: HashMap::new(),
+            blocked_until: HashMap::new(),
+        }
+    }
+
+    pub fn max_per_min(&self) -> usize {
+        self.max_per_min
+    }
+
+    /// Counts a discover served to `enquirer` and returns whether it went over
+    /// the limit, in which case the enquirer is blocked until
+    /// [`release`](Self::release) lets it go.
+    pub fn record(&mut self, enquirer: PeerId, now: i64) -> bool {
+        if self.blocked_until.contains_key(&enquirer) {
+            return false;
+        }
+
+        let served = self.served.entry(enquirer).or_default();
+        while served.front().is_some_and(|&at| now - at >= WINDOW_MS) {
+            served.pop_front();
+        }
+        served.push_back(now);
+        if served.len() <= self.max_per_min {
+            return false;
+        }
+
+        self.served.remove(&enquirer);
+        self.blocked_until.insert(enquirer, now + self.block_ms);
+        true
+    }
+
+    /// Forgets enquirers whose block has run out and those that have been
+    /// quiet for a whole window, returning the ones to unblock.
+    pub fn release(&mut self, now: i64) -> Vec<PeerId> {
+        self.served.retain(|_, served| served.back().is_some_and(|&at| now - at < WINDOW_MS));
+
+        let expired = self
+            .blocked_until
+            .iter()
+            .filter(|(_, until)| **until <= now)
+            .map(|(peer, _)| *peer)
+            .collect::<Vec<_>>();
+        for peer in &expired {
+            self.blocked_until.remove(peer);
+        }
+        expired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_above_the_limit_within_a_minute() {
+        let mut throttle = DiscoverThrottle::new(3, 300_000);
+        let enquirer = PeerId::random();
+
+        assert!(!throttle.record(enquirer, 0));
+        assert!(!throttle.record(enquirer, 20_000));
+        assert!(!throttle.record(enquirer, 40_000));
+        assert!(throttle.record(enquirer, 50_000));
+        // Already blocked, so it is not reported again.
+        assert!(!throttle.record(enquirer, 55_000));
+    }
+
+    #[test]
+    fn old_discovers_leave_the_window() {
+        let mut throttle = DiscoverThrottle::new(2, 300_000);
+        let enquirer = PeerId::random();
+
+        for now in [0, 30_000, 60_000, 90_000, 120_000] {
+            assert!(!throttle.record(enquirer, now), "at {now}");
+        }
+        assert!(throttle.record(enquirer, 130_000));
+    }
+
+    #[test]
+    fn release_unblocks_after_the_block_time() {
+        let mut throttle = DiscoverThrottle::new(1, 300_000);
+        let enquirer = PeerId::random();
+        throttle.record(enquirer, 0);
+        assert!(throttle.record(enquirer, 1_000));
+
+        assert!(throttle.release(300_000).is_empty());
+        assert_eq!(throttle.release(301_000), vec![enquirer]);
+        assert!(!throttle.record(enquirer, 302_000));
+    }
+}